@@ -0,0 +1,255 @@
+//! Alephium addresses: base58 encoded lockup scripts, each belonging to one
+//! of the network's groups.
+
+use anyhow::bail;
+use std::fmt;
+use std::str::FromStr;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Lockup script behind an address, the first decoded byte is its type.
+/// Contract addresses can not receive mining rewards and are rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    P2pkh([u8; 32]), //公钥哈希
+    P2mpkh {
+        keys: Vec<[u8; 32]>, //公钥哈希，分组由第一个决定
+        m: usize,            //m-of-n 多签
+    },
+    P2sh([u8; 32]), //脚本哈希
+}
+
+impl Address {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Address::P2pkh(_) => "p2pkh",
+            Address::P2mpkh { .. } => "p2mpkh",
+            Address::P2sh(_) => "p2sh",
+        }
+    }
+
+    /// Group the address belongs to in a network of `groups` groups,
+    /// derived from its script hint the way the node does.
+    pub fn group(&self, groups: u32) -> u32 {
+        match self {
+            Address::P2pkh(hash) | Address::P2sh(hash) => group_of_hash(hash, groups),
+            Address::P2mpkh { keys, .. } => group_of_hash(&keys[0], groups),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Address::P2pkh(hash) => [&[0u8][..], hash].concat(),
+            Address::P2mpkh { keys, m } => {
+                let mut bytes = vec![1u8, keys.len() as u8];
+                keys.iter().for_each(|key| bytes.extend(key));
+                bytes.push(*m as u8);
+                bytes
+            }
+            Address::P2sh(hash) => [&[2u8][..], hash].concat(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Address> {
+        let (kind, body) = match bytes.split_first() {
+            Some(val) => val,
+            None => bail!("empty address"),
+        };
+        match kind {
+            0 => Ok(Address::P2pkh(hash(body)?)),
+            1 => {
+                let (count, rest) = compact_int(body)?;
+                if count == 0 || rest.len() < count * 32 {
+                    bail!("p2mpkh address with {} keys is truncated", count);
+                }
+                let (keys, rest) = rest.split_at(count * 32);
+                let (m, rest) = compact_int(rest)?;
+                if !rest.is_empty() {
+                    bail!("{} trailing bytes in p2mpkh address", rest.len());
+                }
+                if m == 0 || m > count {
+                    bail!("p2mpkh address needs {} of {} keys", m, count);
+                }
+                let keys = keys.chunks(32).map(hash).collect::<anyhow::Result<_>>()?;
+                Ok(Address::P2mpkh { keys, m })
+            }
+            2 => Ok(Address::P2sh(hash(body)?)),
+            3 => bail!("contract addresses can not receive mining rewards"),
+            _ => bail!("unknown address type {}", kind),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Address::from_bytes(&decode_base58(s)?)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", encode_base58(&self.to_bytes()))
+    }
+}
+
+fn hash(bytes: &[u8]) -> anyhow::Result<[u8; 32]> {
+    if bytes.len() != 32 {
+        bail!("hash must be 32 bytes, got {}", bytes.len());
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(bytes);
+    Ok(hash)
+}
+
+//节点的 CompactInteger.Signed，这里只会出现单字节形式的 0..32
+fn compact_int(bytes: &[u8]) -> anyhow::Result<(usize, &[u8])> {
+    match bytes.split_first() {
+        Some((byte, rest)) if *byte < 0x20 => Ok((*byte as usize, rest)),
+        Some((byte, _)) => bail!("unsupported compact integer {:02x}", byte),
+        None => bail!("truncated address"),
+    }
+}
+
+pub fn decode_base58(input: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    for c in input.bytes() {
+        let mut carry = match ALPHABET.iter().position(|val| *val == c) {
+            Some(digit) => digit as u32,
+            None => bail!("invalid base58 character {:?}", c as char),
+        };
+        //小端存放，逐位乘 58 累加
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    //前导 '1' 对应前导零字节
+    let zeros = input.bytes().take_while(|val| *val == ALPHABET[0]).count();
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Ok(bytes)
+}
+
+pub fn encode_base58(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for byte in bytes {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|val| **val == 0).count();
+    std::iter::repeat_n(ALPHABET[0] as char, zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|val| ALPHABET[*val as usize] as char),
+        )
+        .collect()
+}
+
+//djb 哈希的 4 个字节异或后取模，与节点的 ScriptHint 一致
+fn group_of_hash(hash: &[u8], groups: u32) -> u32 {
+    let hint = hash.iter().fold(5381u32, |acc, byte| {
+        acc.wrapping_shl(5)
+            .wrapping_add(acc)
+            .wrapping_add(*byte as u32)
+    }) | 1;
+    let xor = hint.to_be_bytes().iter().fold(0u8, |acc, byte| acc ^ byte);
+    xor as u32 % groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_base58, encode_base58, Address};
+
+    #[test]
+    fn test_base58() {
+        for (input, expected) in [
+            ("", ""),
+            ("2g", "61"),
+            ("a3gV", "626262"),
+            (
+                "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L",
+                "00eb15231dfceb60925886b67d065299925915aeb172c06647",
+            ),
+            ("1111111111", "00000000000000000000"),
+        ] {
+            assert_eq!(hex::encode(decode_base58(input).unwrap()), expected);
+            assert_eq!(encode_base58(&hex::decode(expected).unwrap()), input);
+        }
+        assert!(decode_base58("0OIl").is_err());
+    }
+
+    #[test]
+    fn test_address() {
+        // one p2pkh address per group, the first one from the node's docs
+        for (index, input) in [
+            "1DrDyTr9RpRsQnDnXo2YRiPzPW4ooHX5LLoqXrqfMrpQH",
+            "14pZpAQmwkyrgcpm7FrmxvNh3mtjutZTA5sGkX7Pjjnfa",
+            "14wj2RhTXzcCXuGLmWbDvbfPSBXXJCF9FqKhCEcDH4QKe",
+            "17QBaVRKLKGEv9V4F5jQJQBiDFsdkvyfLvEVJzs8B36Xt",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let address = input.parse::<Address>().unwrap();
+            assert_eq!(address.kind(), "p2pkh");
+            assert_eq!(address.group(4), index as u32);
+            assert_eq!(address.group(2), index as u32 % 2);
+            assert_eq!(address.to_string(), *input);
+        }
+
+        // 2-of-3 multisig from the node's address tests, grouped by its
+        // first key
+        let input = "X4TqZeAizjDV8yt7XzxDVLywdzmJvLALtdAnjAERtCY3TPkyPXt4A5fxvXAX7UucXPpSYF7amNysNiniqb98vQ5rs9gh12MDXhsAf5kWmbmjXDygxV9AboSj8QR7QK8duaKAkZ";
+        let address = input.parse::<Address>().unwrap();
+        match &address {
+            Address::P2mpkh { keys, m } => {
+                assert_eq!(keys.len(), 3);
+                assert_eq!(*m, 2);
+                assert_eq!(
+                    hex::encode(keys[0]),
+                    "cb9469272d1f2a2d4584d23deaf90a62fa80df95ca35767a6479706404306788"
+                );
+            }
+            _ => panic!("{} is not a multisig address", input),
+        }
+        assert_eq!(address.group(4), 2);
+        assert_eq!(address.to_string(), input);
+
+        // a script hash, the type byte followed by the hash
+        let address = "tgx7VNFoP9DJiFMFgXXtafQZkUvyEdDHT9ryamHJYrjp"
+            .parse::<Address>()
+            .unwrap();
+        assert_eq!(address, Address::P2sh([0xffu8; 32]));
+        assert_eq!(address.group(4), 2);
+
+        // contract
+        assert!("2BvGUzA1QBBx5HL8fuhiBmtD5zyaHyqHNwCpJSgi54oz6"
+            .parse::<Address>()
+            .is_err());
+        // 3-of-2 multisig
+        assert!("2jVYyNjBjY4JuZrV6c6A8eNXPmb6BixSaFs7zBaUCZnuDNz9RsKRieeLsYMPrHmaeQ3dtTjmk9gr8iYixbAFirr5ere"
+            .parse::<Address>()
+            .is_err());
+        // 25 bytes is not a lockup script
+        assert!("1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"
+            .parse::<Address>()
+            .is_err());
+    }
+}
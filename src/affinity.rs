@@ -0,0 +1,200 @@
+//! Placement of worker threads on cores: pinning, skipped cores and spreading
+//! across NUMA nodes.
+
+use crate::config::AffinityConfig;
+use serde_derive::Serialize;
+use std::fs;
+
+const NODE_DIR: &str = "/sys/devices/system/node";
+
+/// Cores of each NUMA node, a single node when the machine reports none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    nodes: Vec<Vec<usize>>,
+}
+
+/// Where one worker runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Placement {
+    pub core: usize,
+    pub node: usize,
+}
+
+impl Topology {
+    pub fn new(nodes: Vec<Vec<usize>>) -> Topology {
+        Topology { nodes }
+    }
+
+    pub fn detect() -> Topology {
+        let mut nodes = vec![];
+        if let Ok(dir) = fs::read_dir(NODE_DIR) {
+            let mut ids: Vec<usize> = dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    name.strip_prefix("node")?.parse::<usize>().ok()
+                })
+                .collect();
+            ids.sort_unstable();
+            for id in ids {
+                let path = format!("{}/node{}/cpulist", NODE_DIR, id);
+                if let Some(cores) = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|val| parse_list(&val))
+                {
+                    nodes.push(cores);
+                }
+            }
+        }
+        nodes.retain(|val| !val.is_empty());
+        if nodes.is_empty() {
+            let cores = core_affinity::get_core_ids()
+                .map(|ids| ids.into_iter().map(|val| val.id).collect())
+                .unwrap_or_else(|| (0..num_cpus::get()).collect());
+            nodes.push(cores);
+        }
+        Topology { nodes }
+    }
+
+    pub fn node_of(&self, core: usize) -> Option<usize> {
+        self.nodes.iter().position(|val| val.contains(&core))
+    }
+
+    /// Cores for `workers` workers, `None` when pinning is off. Configured
+    /// cores (all cores by default) minus skipped ones are handed out in
+    /// order, or round robin across nodes with `numa_spread`, and reused
+    /// when there are more workers than cores.
+    pub fn assign(&self, conf: &AffinityConfig, workers: usize) -> Option<Vec<Placement>> {
+        if !conf.enabled() {
+            return None;
+        }
+        let usable = |core: &usize| {
+            !conf.skip.contains(core) && conf.cores.as_ref().is_none_or(|val| val.contains(core))
+        };
+        let nodes: Vec<Vec<usize>> = self
+            .nodes
+            .iter()
+            .map(|cores| cores.iter().copied().filter(usable).collect())
+            .collect();
+        //不在任何节点里的显式核，归到节点 0
+        let unknown = conf
+            .cores
+            .iter()
+            .flatten()
+            .filter(|core| self.node_of(**core).is_none() && usable(core))
+            .map(|core| Placement {
+                core: *core,
+                node: 0,
+            });
+        let mut order: Vec<Placement> = vec![];
+        if conf.numa_spread {
+            let depth = nodes.iter().map(|val| val.len()).max().unwrap_or(0);
+            for index in 0..depth {
+                for (node, cores) in nodes.iter().enumerate() {
+                    if let Some(core) = cores.get(index) {
+                        order.push(Placement { core: *core, node });
+                    }
+                }
+            }
+        } else {
+            for (node, cores) in nodes.iter().enumerate() {
+                order.extend(cores.iter().map(|core| Placement { core: *core, node }));
+            }
+        }
+        order.extend(unknown);
+        if order.is_empty() {
+            warn!("no core left to pin workers to, running unpinned");
+            return None;
+        }
+        Some(
+            (0..workers)
+                .map(|index| order[index % order.len()])
+                .collect(),
+        )
+    }
+}
+
+/// Pins the calling thread.
+pub fn pin(core: usize) {
+    core_affinity::set_for_current(core_affinity::CoreId { id: core });
+}
+
+//"0-3,8,10-11" => [0, 1, 2, 3, 8, 10, 11]
+pub fn parse_list(input: &str) -> Option<Vec<usize>> {
+    let mut cores = vec![];
+    for part in input.trim().split(',').filter(|val| !val.trim().is_empty()) {
+        match part.trim().split_once('-') {
+            Some((start, end)) => {
+                let start = start.trim().parse::<usize>().ok()?;
+                let end = end.trim().parse::<usize>().ok()?;
+                //倒序的范围写错了，不当作空范围
+                if start > end {
+                    return None;
+                }
+                cores.extend(start..=end);
+            }
+            None => cores.push(part.trim().parse::<usize>().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_list, Placement, Topology};
+    use crate::config::AffinityConfig;
+
+    fn cores(placements: &[Placement]) -> Vec<usize> {
+        placements.iter().map(|val| val.core).collect()
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_list(""), Some(vec![]));
+        assert_eq!(parse_list("a-3"), None);
+        assert_eq!(parse_list("7-0"), None);
+        assert_eq!(parse_list("3-3"), Some(vec![3]));
+    }
+
+    #[test]
+    fn test_assign() {
+        let topology = Topology::new(vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        assert_eq!(topology.assign(&AffinityConfig::default(), 4), None);
+
+        let conf = AffinityConfig {
+            pin: true,
+            skip: vec![0, 1],
+            ..Default::default()
+        };
+        let placements = topology.assign(&conf, 8).unwrap();
+        assert_eq!(cores(&placements), vec![2, 3, 4, 5, 6, 7, 2, 3]);
+        assert_eq!(placements[2], Placement { core: 4, node: 1 });
+
+        let conf = AffinityConfig {
+            numa_spread: true,
+            skip: vec![0],
+            ..Default::default()
+        };
+        let placements = topology.assign(&conf, 4).unwrap();
+        assert_eq!(cores(&placements), vec![1, 4, 2, 5]);
+
+        let conf = AffinityConfig {
+            cores: Some(vec![6, 1, 9]),
+            ..Default::default()
+        };
+        let placements = topology.assign(&conf, 3).unwrap();
+        assert_eq!(cores(&placements), vec![1, 6, 9]);
+        assert_eq!(placements[1].node, 1);
+
+        let conf = AffinityConfig {
+            pin: true,
+            skip: (0..8).collect(),
+            ..Default::default()
+        };
+        assert_eq!(topology.assign(&conf, 2), None);
+    }
+}
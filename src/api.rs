@@ -0,0 +1,145 @@
+use crate::control::{Command, Control};
+use crate::counter::SharedCounter;
+use crate::http::{self, Request, Response};
+use crate::metrics;
+use tokio::net::TcpListener;
+
+/// Serves the miner stats over http:
+/// `GET /stats` returns the `Counter` snapshot as json,
+/// `GET /health` answers `ok` as long as the miner is running,
+/// `GET /metrics` returns the same stats in Prometheus text format,
+/// `POST /control` runs a `control::Command` such as `{"command": "pause"}`
+/// and returns the resulting status, `GET /control` only the status.
+pub fn serve(listener: TcpListener, counter: SharedCounter, control: Option<Control>) {
+    http::serve(listener, move |req| {
+        let counter = counter.clone();
+        let control = control.clone();
+        async move { route(&counter, control.as_ref(), req).await }
+    });
+}
+
+async fn route(counter: &SharedCounter, control: Option<&Control>, req: Request) -> Response {
+    match (req.method.as_str(), req.path.as_str()) {
+        ("GET", "/control") | ("POST", "/control") => {
+            let control = match control {
+                Some(control) => control,
+                None => return Response::text(404, "not found"),
+            };
+            let command = if req.method == "GET" {
+                Command::Status
+            } else {
+                match serde_json::from_str::<Command>(&req.body) {
+                    Ok(command) => command,
+                    Err(err) => return Response::text(400, &format!("invalid command: {}", err)),
+                }
+            };
+            match control.send(command).await {
+                Ok(status) => Response::json(&status),
+                Err(err) => Response::text(409, &err),
+            }
+        }
+        ("GET", "/stats") => Response::json(&counter.lock().stats()),
+        ("GET", "/health") => Response::text(200, "ok"),
+        ("GET", "/metrics") => Response {
+            status: 200,
+            content_type: metrics::CONTENT_TYPE,
+            body: metrics::render(&counter.lock().stats()),
+        },
+        (_, "/stats") | (_, "/health") | (_, "/metrics") | (_, "/control") => {
+            Response::text(405, "method not allowed")
+        }
+        _ => Response::text(404, "not found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::route;
+    use crate::control::{Command, Control, Status};
+    use crate::counter::Counter;
+    use crate::http::Request;
+    use crate::model::Job;
+
+    fn get(path: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            body: "".to_string(),
+        }
+    }
+
+    fn post(path: &str, body: &str) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control() {
+        let counter = Counter::new().shared();
+        assert_eq!(route(&counter, None, get("/control")).await.status, 404);
+
+        let (control, mut rx) = Control::channel();
+        tokio::spawn(async move {
+            while let Some((command, reply)) = rx.recv().await {
+                let ret = match command {
+                    Command::SetWorkers { count } if count > 8 => Err("too many".to_string()),
+                    command => Ok(Status {
+                        paused: command == Command::Pause,
+                        ..Default::default()
+                    }),
+                };
+                let _ = reply.send(ret);
+            }
+        });
+        let res = route(
+            &counter,
+            Some(&control),
+            post("/control", r#"{"command": "pause"}"#),
+        )
+        .await;
+        assert_eq!(res.status, 200);
+        let status: serde_json::Value = serde_json::from_str(&res.body).unwrap();
+        assert_eq!(status["paused"], true);
+        let res = route(&counter, Some(&control), get("/control")).await;
+        assert_eq!(res.status, 200);
+        let body = r#"{"command": "set_workers", "count": 9}"#;
+        let res = route(&counter, Some(&control), post("/control", body)).await;
+        assert_eq!((res.status, res.body.as_str()), (409, "too many"));
+        let res = route(&counter, Some(&control), post("/control", "{}")).await;
+        assert_eq!(res.status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let counter = Counter::new().shared();
+        counter.lock().connected("127.0.0.1:10973");
+        counter.lock().job_received(&Job {
+            from: 1,
+            to: 2,
+            target: vec![0xff; 31],
+            ..Default::default()
+        });
+        counter.lock().submitted();
+        counter.lock().submit_result(1, 2, false, false);
+
+        let res = route(&counter, None, get("/stats")).await;
+        assert_eq!(res.status, 200);
+        let stats: serde_json::Value = serde_json::from_str(&res.body).unwrap();
+        assert_eq!(stats["connection"]["connected"], true);
+        assert_eq!(stats["connection"]["endpoint"], "127.0.0.1:10973");
+        assert_eq!(stats["submitted"], 1);
+        assert_eq!(stats["rejected"], 1);
+        assert_eq!(stats["shares_rejected"], 0);
+        assert_eq!(stats["chains"][0]["from"], 1);
+        assert_eq!(stats["chains"][0]["to"], 2);
+
+        assert_eq!(route(&counter, None, get("/health")).await.status, 200);
+        let res = route(&counter, None, get("/metrics")).await;
+        assert_eq!(res.status, 200);
+        assert!(res.body.contains("alephium_miner_submitted_total 1"));
+        assert_eq!(route(&counter, None, get("/nothing")).await.status, 404);
+    }
+}
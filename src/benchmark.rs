@@ -0,0 +1,331 @@
+//! `benchmark` subcommand: runs the mining loop of `Worker` on a synthetic
+//! job, for every hash implementation and thread count. `--autotune` uses the
+//! same runs to pick the fastest settings.
+
+use crate::constant;
+use crate::dashboard::human;
+use crate::model::{Job, WorkUnit};
+use crate::pow::HashImpl;
+use crate::task::Task;
+use crate::worker::Worker;
+use serde_derive::Serialize;
+use std::thread;
+use std::time::Duration;
+
+const HEADER_SIZE: usize = 302;
+//自动调优尝试的检查点步长
+const TUNE_STEPS: [u64; 3] = [10_000, 100_000, 1_000_000];
+//候选设置至少快这么多才替换当前最优，避免因测量误差选中大步长
+const TUNE_MARGIN: f64 = 0.02;
+
+/// One measurement: `threads` workers mining with `hash_impl`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Run {
+    pub backend: String,
+    pub hash_impl: HashImpl,
+    pub threads: usize,
+    pub mining_steps: u64,
+    pub hashes: u64,
+    pub seconds: f64,
+    pub hash_rate: f64,
+    pub per_thread: f64,
+    pub efficiency: f64, //相对最少线程数的单线程算力
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub version: &'static str,
+    pub time: String,
+    pub cpus: usize,
+    pub physical_cpus: usize,
+    pub duration: f64, //每轮秒数
+    pub runs: Vec<Run>,
+}
+
+/// Settings picked by `Benchmark::autotune`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tuning {
+    pub threads: usize,
+    pub mining_steps: u64,
+    pub hash_impl: HashImpl,
+    pub hash_rate: f64,
+}
+
+pub struct Benchmark {
+    backend: String,
+    duration: Duration,
+    threads: Vec<usize>,
+    mining_steps: u64,
+    hash_impls: Vec<HashImpl>,
+}
+
+impl Benchmark {
+    pub fn new(backend: &str) -> Benchmark {
+        Benchmark {
+            backend: backend.to_string(),
+            duration: Duration::from_secs(10),
+            threads: default_threads(num_cpus::get()),
+            mining_steps: constant::MINING_STEPS,
+            hash_impls: HashImpl::ALL.to_vec(),
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: Vec<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn with_mining_steps(mut self, mining_steps: u64) -> Self {
+        self.mining_steps = mining_steps;
+        self
+    }
+
+    pub fn with_hash_impls(mut self, hash_impls: Vec<HashImpl>) -> Self {
+        self.hash_impls = hash_impls;
+        self
+    }
+
+    fn check_backend(&self) -> anyhow::Result<()> {
+        if self.backend != "cpu" {
+            anyhow::bail!("backend {} has no mining implementation", self.backend);
+        }
+        Ok(())
+    }
+
+    //base 为同一实现下第一轮的单线程算力
+    fn run_one(&self, hash_impl: HashImpl, threads: usize, steps: u64, base: Option<f64>) -> Run {
+        let (hashes, seconds, hash_rate) = measure(hash_impl, threads, steps, self.duration);
+        let per_thread = hash_rate / threads as f64;
+        let base = base.unwrap_or(per_thread);
+        Run {
+            backend: self.backend.clone(),
+            hash_impl,
+            threads,
+            mining_steps: steps,
+            hashes,
+            seconds,
+            hash_rate,
+            per_thread,
+            efficiency: if base > 0f64 { per_thread / base } else { 0f64 },
+        }
+    }
+
+    /// Runs every combination, `progress` is called after each run.
+    pub fn run<F: FnMut(&Run)>(&self, mut progress: F) -> anyhow::Result<Report> {
+        self.check_backend()?;
+        let mut runs = vec![];
+        for hash_impl in &self.hash_impls {
+            let mut base = None;
+            for threads in &self.threads {
+                let run = self.run_one(*hash_impl, *threads, self.mining_steps, base);
+                base.get_or_insert(run.per_thread);
+                progress(&run);
+                runs.push(run);
+            }
+        }
+        Ok(Report {
+            version: env!("CARGO_PKG_VERSION"),
+            time: chrono::Utc::now().to_rfc3339(),
+            cpus: num_cpus::get(),
+            physical_cpus: num_cpus::get_physical(),
+            duration: self.duration.as_secs_f64(),
+            runs,
+        })
+    }
+
+    /// Picks the fastest hash implementation, then thread count (physical
+    /// or logical cores), then step size, one setting at a time.
+    pub fn autotune<F: FnMut(&Run)>(&self, mut progress: F) -> anyhow::Result<Tuning> {
+        self.check_backend()?;
+        let mut threads = vec![num_cpus::get_physical(), num_cpus::get()];
+        threads.dedup();
+        let mut best = Tuning {
+            threads: threads[0],
+            mining_steps: self.mining_steps,
+            hash_impl: HashImpl::default(),
+            hash_rate: 0f64,
+        };
+        let mut try_run = |best: &mut Tuning, candidate: Tuning| {
+            let run = self.run_one(
+                candidate.hash_impl,
+                candidate.threads,
+                candidate.mining_steps,
+                None,
+            );
+            progress(&run);
+            if run.hash_rate > best.hash_rate * (1f64 + TUNE_MARGIN) {
+                *best = Tuning {
+                    hash_rate: run.hash_rate,
+                    ..candidate
+                };
+            }
+        };
+        for hash_impl in &self.hash_impls {
+            let candidate = Tuning {
+                hash_impl: *hash_impl,
+                ..best.clone()
+            };
+            try_run(&mut best, candidate);
+        }
+        for val in threads.iter().skip(1) {
+            let candidate = Tuning {
+                threads: *val,
+                ..best.clone()
+            };
+            try_run(&mut best, candidate);
+        }
+        for steps in TUNE_STEPS.iter().filter(|val| **val != self.mining_steps) {
+            let candidate = Tuning {
+                mining_steps: *steps,
+                ..best.clone()
+            };
+            try_run(&mut best, candidate);
+        }
+        Ok(best)
+    }
+}
+
+//1, 2, 4, ... 直到逻辑核数
+pub fn default_threads(cpus: usize) -> Vec<usize> {
+    let mut threads = vec![];
+    let mut n = 1;
+    while n < cpus {
+        threads.push(n);
+        n *= 2;
+    }
+    threads.push(cpus.max(1));
+    threads
+}
+
+//任务目标不可达，工作线程一直计算到被通知，再从各自交回的任务取计算次数和时长
+fn measure(hash_impl: HashImpl, threads: usize, steps: u64, duration: Duration) -> (u64, f64, f64) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(threads * 2);
+    let (sender, receiver) = crossbeam::channel::unbounded();
+    let header: Vec<u8> = (0..HEADER_SIZE).map(|_| rand::random::<u8>()).collect();
+    let mut notifiers = vec![];
+    let mut handles = vec![];
+    for _ in 0..threads {
+        let mut worker = Worker::new(tx.clone(), receiver.clone())
+            .with_hash_impl(hash_impl)
+            .with_mining_steps(steps);
+        notifiers.push(worker.notifier());
+        handles.push(thread::spawn(move || worker.work()));
+        let task = Task::new().with_job(Job {
+            header: header.clone(),
+            target: vec![0; 32],
+            ..Default::default()
+        });
+        sender.send(WorkUnit::TaskReq(task)).unwrap();
+    }
+    thread::sleep(duration);
+    notifiers.iter().for_each(|val| val.notify());
+    let (mut hashes, mut seconds, mut hash_rate) = (0, 0f64, 0f64);
+    for _ in 0..threads {
+        let task = rx.blocking_recv().expect("worker exited");
+        let elapsed = task.elapsed().as_secs_f64();
+        hashes += task.hash_count();
+        seconds = seconds.max(elapsed);
+        hash_rate += task.hash_count() as f64 / elapsed;
+    }
+    //关闭任务队列，工作线程退出
+    drop(sender);
+    handles.into_iter().for_each(|val| val.join().unwrap());
+    (hashes, seconds, hash_rate)
+}
+
+/// Text rendering of a report: one row per run, then blake3-merkle against
+/// blake3 at each thread count.
+pub fn describe(report: &Report) -> String {
+    let mut out = format!(
+        "{} cpus ({} physical), {}s per run\n{:<14} {:>7} {:>12} {:>12} {:>10}\n",
+        report.cpus,
+        report.physical_cpus,
+        report.duration,
+        "hash",
+        "threads",
+        "hash/s",
+        "per thread",
+        "efficiency"
+    );
+    for run in &report.runs {
+        out.push_str(&format!(
+            "{:<14} {:>7} {:>12} {:>12} {:>9.1}%\n",
+            run.hash_impl.to_string(),
+            run.threads,
+            human(run.hash_rate),
+            human(run.per_thread),
+            run.efficiency * 100f64
+        ));
+    }
+    for run in report
+        .runs
+        .iter()
+        .filter(|val| val.hash_impl == HashImpl::Blake3Merkle)
+    {
+        let base = report
+            .runs
+            .iter()
+            .find(|val| val.hash_impl == HashImpl::Blake3 && val.threads == run.threads);
+        if let Some(base) = base.filter(|val| val.hash_rate > 0f64) {
+            out.push_str(&format!(
+                "blake3-merkle / blake3 at {} threads: {:.2}x\n",
+                run.threads,
+                run.hash_rate / base.hash_rate
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_threads, describe, Benchmark};
+    use crate::pow::HashImpl;
+    use std::time::Duration;
+
+    #[test]
+    fn test_benchmark() {
+        assert_eq!(default_threads(1), vec![1]);
+        assert_eq!(default_threads(6), vec![1, 2, 4, 6]);
+        assert_eq!(default_threads(8), vec![1, 2, 4, 8]);
+
+        let report = Benchmark::new("cpu")
+            .with_duration(Duration::from_millis(500))
+            .with_threads(vec![1, 2])
+            .run(|_| {})
+            .unwrap();
+        assert_eq!(report.runs.len(), 4);
+        assert!(report.runs.iter().all(|val| val.hashes > 0));
+        assert_eq!(report.runs[0].efficiency, 1f64);
+        assert_eq!(report.runs[2].hash_impl, HashImpl::Blake3Merkle);
+        let text = describe(&report);
+        assert!(text.contains("blake3-merkle / blake3 at 2 threads"));
+
+        assert!(Benchmark::new("nvidia").run(|_| {}).is_err());
+    }
+
+    #[test]
+    fn test_autotune() {
+        let mut runs = vec![];
+        let tuning = Benchmark::new("cpu")
+            .with_duration(Duration::from_millis(200))
+            .with_hash_impls(vec![HashImpl::Blake3])
+            .autotune(|run| runs.push(run.clone()))
+            .unwrap();
+        // the kept run is within the margin of the fastest one
+        let best = runs.iter().map(|val| val.hash_rate).fold(0f64, f64::max);
+        let kept = runs
+            .iter()
+            .find(|val| val.hash_rate == tuning.hash_rate)
+            .unwrap();
+        assert!(tuning.hash_rate * 1.02 >= best);
+        assert_eq!(tuning.mining_steps, kept.mining_steps);
+        assert_eq!(tuning.threads, kept.threads);
+        assert_eq!(tuning.hash_impl, HashImpl::Blake3);
+    }
+}
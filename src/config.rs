@@ -1,7 +1,496 @@
-#[derive(Debug, Clone, Default)]
+use crate::address::Address;
+use crate::constant;
+use crate::counter;
+use crate::network::{self, Network};
+use crate::pow::HashImpl;
+use crate::serder;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Arc;
+
+/// Config file read when `--config` is not given, if it exists.
+pub const DEFAULT_PATH: &str = "alephium-miner.toml";
+
+const HOSTNAME: &str = "/proc/sys/kernel/hostname";
+
+/// Reads the config again the way it was read at startup, command line
+/// options included.
+pub type Loader = Arc<dyn Fn() -> anyhow::Result<Config> + Send + Sync>;
+
+/// Settings applied to a running miner on reload, a name covers the
+/// settings nested under it. Changes to any other setting need a restart.
+pub const RELOADABLE: [&str; 14] = [
+    "ip",
+    "port",
+    "endpoints",
+    "chains",
+    "worker_num",
+    "mining_steps",
+    "hash_impl",
+    "throttle.cpu",
+    "throttle.hash_rate",
+    "share_target",
+    "job_check",
+    "notify",
+    "stats_interval",
+    "log.level",
+];
+
+/// Most workers a miner runs, more threads than this only contend for the
+/// cores.
+pub fn max_workers() -> usize {
+    num_cpus::get() * constant::MAX_WORKERS_PER_CPU
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub rig: Option<String>,      //矿机名，默认主机名
+    pub addresses: Vec<String>,   //收益地址，第 g 个属于分组 g
+    pub network: Option<Network>, //网络配置，决定默认端口、分组数和任务检查
+    pub groups: Option<u32>,      //网络分组数，None 表示按网络配置或由任务数推断
     pub ip: String,
     pub port: String,
+    pub endpoints: Vec<String>, //备用节点 host:port，ip:port 连不上时依次尝试
+    pub chains: Vec<(u32, u32)>, //只挖这些链 (from, to)，空表示全部
     pub miner_type: String,
     pub worker_num: usize,
+    pub mining_steps: u64, //工作线程检查点之间的计算次数
+    pub hash_impl: HashImpl,
+    pub affinity: AffinityConfig,
+    pub throttle: ThrottleConfig,
+    pub job_check: JobCheckConfig,
+    #[serde(with = "serder::hex_option")]
+    pub share_target: Option<Vec<u8>>, //矿池模式下的份额目标，None 表示 solo 挖矿
+    pub notify: NotifyConfig,
+    pub api: Option<String>,            //stats api 监听地址，None 表示不开启
+    pub api_control: bool,              //stats api 同时接受 /control 命令，默认关闭
+    pub control_socket: Option<String>, //本地控制 socket 路径，None 表示不开启
+    pub history_size: usize,            //保留最近完成任务的条数
+    pub stats_interval: u64,            //打印统计的间隔，秒
+    pub tui: bool,                      //终端仪表盘
+    pub log: LogConfig,
+    pub record: Option<String>, //记录与节点往来的全部帧
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rig: None,
+            addresses: vec![],
+            network: None,
+            groups: None,
+            ip: "127.0.0.1".to_string(),
+            port: "10973".to_string(),
+            endpoints: vec![],
+            chains: vec![],
+            miner_type: "cpu".to_string(),
+            worker_num: num_cpus::get(),
+            mining_steps: constant::MINING_STEPS,
+            hash_impl: Default::default(),
+            affinity: Default::default(),
+            throttle: Default::default(),
+            job_check: Default::default(),
+            share_target: None,
+            notify: Default::default(),
+            api: None,
+            api_control: false,
+            control_socket: None,
+            history_size: counter::DEFAULT_HISTORY_SIZE,
+            stats_interval: 120,
+            tui: false,
+            log: Default::default(),
+            record: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a toml config file, missing settings keep their defaults.
+    pub fn load(path: &str) -> anyhow::Result<Config> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Whether the config file at `path` sets the top level setting `name`.
+    pub fn sets(path: &str, name: &str) -> bool {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::value::Table>(&content).ok())
+            .is_some_and(|table| table.contains_key(name))
+    }
+
+    /// Writes the given top level settings into the config file at `path`,
+    /// creating it if needed and keeping the other settings.
+    pub fn persist(path: &str, values: toml::value::Table) -> anyhow::Result<()> {
+        let mut table = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str::<toml::value::Table>(&content)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => return Err(err.into()),
+        };
+        table.extend(values);
+        fs::write(path, toml::to_string(&toml::Value::Table(table))?)?;
+        Ok(())
+    }
+
+    /// Configured rig name, the host name otherwise.
+    pub fn rig_name(&self) -> String {
+        self.rig.clone().unwrap_or_else(|| {
+            fs::read_to_string(HOSTNAME)
+                .map(|val| val.trim().to_string())
+                .ok()
+                .filter(|val| !val.is_empty())
+                .unwrap_or_else(|| "miner".to_string())
+        })
+    }
+
+    /// Node endpoints in the order they are tried, ip:port first.
+    pub fn nodes(&self) -> Vec<String> {
+        let mut nodes = vec![format!("{}:{}", self.ip, self.port)];
+        nodes.extend(self.endpoints.iter().cloned());
+        nodes
+    }
+
+    /// Whether jobs of the chain are mined, all chains unless pinned.
+    pub fn mines(&self, from: u32, to: u32) -> bool {
+        self.chains.is_empty() || self.chains.contains(&(from, to))
+    }
+
+    /// Checks settings the miner can not run with: the worker count, the
+    /// share target, the group count, the pinned chains and the addresses,
+    /// which are optional but when given there is one per group and each
+    /// belongs to its group.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.worker_num > max_workers() {
+            anyhow::bail!("at most {} workers, got {}", max_workers(), self.worker_num);
+        }
+        if let Some(target) = self.share_target.as_ref() {
+            if target.is_empty() || target.len() > 32 {
+                anyhow::bail!("share target must be 1 to 32 bytes, got {}", target.len());
+            }
+        }
+        if let Some(groups) = self.groups {
+            if groups == 0 || groups > network::MAX_GROUPS {
+                anyhow::bail!(
+                    "groups must be between 1 and {}, got {}",
+                    network::MAX_GROUPS,
+                    groups
+                );
+            }
+        }
+        let groups = network::Groups::from_config(self).get();
+        //分组数未配置时由任务推断，只能按上限检查
+        let limit = if self.groups.is_some() || self.network.is_some() {
+            groups
+        } else {
+            network::MAX_GROUPS
+        };
+        if let Some((from, to)) = self
+            .chains
+            .iter()
+            .find(|(from, to)| *from >= limit || *to >= limit)
+        {
+            anyhow::bail!("chain {}-{} is not in a {} group network", from, to, limit);
+        }
+        if self.addresses.is_empty() {
+            return Ok(());
+        }
+        if self.addresses.len() != groups as usize {
+            anyhow::bail!(
+                "{} addresses given, one per group is needed: {}",
+                self.addresses.len(),
+                groups
+            );
+        }
+        for (index, val) in self.addresses.iter().enumerate() {
+            let address = val
+                .parse::<Address>()
+                .map_err(|err| anyhow::anyhow!("address {} {}: {}", index, val, err))?;
+            let group = address.group(groups);
+            if group != index as u32 {
+                anyhow::bail!(
+                    "address {} {} is a {} address of group {}",
+                    index,
+                    val,
+                    address.kind(),
+                    group
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the settings that differ in `new`, nested ones like
+    /// `throttle.cpu`.
+    pub fn changes(&self, new: &Config) -> Vec<String> {
+        let old = serde_json::to_value(self).expect("encode config error");
+        let new = serde_json::to_value(new).expect("encode config error");
+        let mut changes = vec![];
+        diff("", &old, &new, &mut changes);
+        changes
+    }
+}
+
+/// Whether a change of the setting `name` can be applied without restart.
+pub fn reloadable(name: &str) -> bool {
+    RELOADABLE
+        .iter()
+        .any(|val| name == *val || name.starts_with(&format!("{}.", val)))
+}
+
+fn diff(prefix: &str, old: &serde_json::Value, new: &serde_json::Value, changes: &mut Vec<String>) {
+    match (old, new) {
+        (serde_json::Value::Object(old), serde_json::Value::Object(new)) => {
+            for (key, val) in old {
+                let name = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                diff(&name, val, &new[key], changes);
+            }
+        }
+        _ if old != new => changes.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// Where to report found blocks once the node answered the submission.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub command: Option<String>, //shell 命令，事件通过环境变量传入
+    pub file: Option<String>,    //追加写入的 JSON lines 文件
+    pub webhook: Option<String>, //http://host:port/path，POST JSON
+}
+
+/// Pinning of worker threads to cores.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AffinityConfig {
+    pub pin: bool,                 //每个工作线程绑定一个核
+    pub cores: Option<Vec<usize>>, //只使用这些核，None 表示全部
+    pub skip: Vec<usize>,          //留给节点等其他进程的核
+    pub numa_spread: bool,         //工作线程轮流分布到各 NUMA 节点
+}
+
+impl AffinityConfig {
+    /// Any of the settings turns pinning on.
+    pub fn enabled(&self) -> bool {
+        self.pin || self.cores.is_some() || !self.skip.is_empty() || self.numa_spread
+    }
+}
+
+/// Limits for machines shared with other services.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    pub cpu: Option<f64>,       //每个工作线程占用单核的百分比
+    pub hash_rate: Option<f64>, //全部工作线程合计的算力上限
+    pub idle_load: Option<f64>, //其他进程的 1 分钟负载超过此值时暂停挖矿
+}
+
+/// Sanity checks on the jobs the node sends.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobCheckConfig {
+    pub policy: JobPolicy,
+    pub min_zero_bits: Option<u32>, //目标至少的前导零位数，默认取网络配置的
+}
+
+impl JobCheckConfig {
+    pub fn min_zero_bits(&self, network: Option<Network>) -> u32 {
+        self.min_zero_bits
+            .or_else(|| network.map(|val| val.profile().min_zero_bits))
+            .unwrap_or(network::MIN_ZERO_BITS)
+    }
+}
+
+/// What to do with a batch of jobs that fails the checks.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPolicy {
+    #[default]
+    Skip, //丢弃有问题的任务，其余照常计算
+    Reconnect, //丢弃整批任务并重连节点
+    Abort,     //退出
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: Option<String>, //error、warn、info、debug、trace，设置 RUST_LOG 时不生效
+    pub format: LogFormat,
+    pub file: Option<String>, //不设置时写 stderr
+    pub max_size: u64,        //单个日志文件上限，MB
+    pub max_files: usize,     //保留的旧日志文件数
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: None,
+            format: LogFormat::Text,
+            file: None,
+            max_size: 100,
+            max_files: 5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reloadable, Config, JobCheckConfig, JobPolicy, LogFormat};
+    use crate::network::Network;
+    use crate::pow::HashImpl;
+    use std::fs;
+
+    #[test]
+    fn test_parse() {
+        let config: Config = toml::from_str(
+            r#"
+            ip = "10.0.0.2"
+            share_target = "00ff"
+            api = "127.0.0.1:8080"
+            endpoints = ["10.0.0.3:10973"]
+            chains = [[0, 1], [2, 3]]
+
+            [log]
+            format = "json"
+
+            [notify]
+            file = "blocks.jsonl"
+
+            [affinity]
+            skip = [0, 1]
+            numa_spread = true
+
+            [throttle]
+            cpu = 50
+            idle_load = 1.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ip, "10.0.0.2");
+        assert_eq!(config.port, "10973");
+        assert_eq!(config.share_target, Some(vec![0, 0xff]));
+        assert_eq!(config.notify.file.as_deref(), Some("blocks.jsonl"));
+        assert_eq!(config.notify.webhook, None);
+        assert_eq!(config.api.as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(config.history_size, 1000);
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.log.max_files, 5);
+        assert_eq!(config.mining_steps, 100000);
+        assert_eq!(config.hash_impl, HashImpl::Blake3);
+        assert_eq!(config.affinity.skip, vec![0, 1]);
+        assert!(config.affinity.numa_spread && config.affinity.enabled());
+        assert!(!Config::default().affinity.enabled());
+        assert_eq!(config.throttle.cpu, Some(50f64));
+        assert_eq!(config.throttle.hash_rate, None);
+        assert_eq!(config.throttle.idle_load, Some(1.5));
+        assert_eq!(config.nodes(), vec!["10.0.0.2:10973", "10.0.0.3:10973"]);
+        assert!(config.mines(2, 3) && !config.mines(1, 0));
+        assert!(Config::default().mines(1, 0));
+    }
+
+    #[test]
+    fn test_persist() {
+        let path = std::env::temp_dir().join(format!("miner-{}.toml", rand::random::<u32>()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "ip = \"10.0.0.2\"\n\n[log]\nformat = \"json\"\n").unwrap();
+        let mut values = toml::value::Table::new();
+        values.insert("worker_num".to_string(), 3.into());
+        values.insert("hash_impl".to_string(), "blake3-merkle".into());
+        Config::persist(path, values).unwrap();
+        let config = Config::load(path).unwrap();
+        assert_eq!(config.ip, "10.0.0.2");
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.worker_num, 3);
+        assert_eq!(config.hash_impl, HashImpl::Blake3Merkle);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Config::default();
+        assert!(old.changes(&old.clone()).is_empty());
+        let mut new = old.clone();
+        new.worker_num += 1;
+        new.throttle.cpu = Some(50f64);
+        new.log.format = LogFormat::Json;
+        new.notify.file = Some("blocks.jsonl".to_string());
+        assert_eq!(
+            old.changes(&new),
+            vec!["log.format", "notify.file", "throttle.cpu", "worker_num"]
+        );
+        assert!(reloadable("worker_num") && reloadable("notify.file"));
+        assert!(reloadable("job_check.policy"));
+        assert!(reloadable("endpoints") && reloadable("chains"));
+        assert!(!reloadable("log.format") && !reloadable("throttle.idle_load"));
+    }
+
+    #[test]
+    fn test_job_check() {
+        let conf: JobCheckConfig = toml::from_str("policy = \"reconnect\"").unwrap();
+        assert_eq!(conf.policy, JobPolicy::Reconnect);
+        assert_eq!(conf.min_zero_bits(None), 1);
+        assert_eq!(conf.min_zero_bits(Some(Network::Mainnet)), 37);
+        let conf = JobCheckConfig {
+            min_zero_bits: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(conf.min_zero_bits(Some(Network::Mainnet)), 8);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        config.addresses = vec!["1DrDyTr9RpRsQnDnXo2YRiPzPW4ooHX5LLoqXrqfMrpQH".to_string()];
+        assert!(config.validate().is_err());
+        config.addresses = vec!["0OIl".to_string(); 4];
+        assert!(config.validate().is_err());
+        config.addresses = vec![
+            "1DrDyTr9RpRsQnDnXo2YRiPzPW4ooHX5LLoqXrqfMrpQH".to_string(),
+            "14pZpAQmwkyrgcpm7FrmxvNh3mtjutZTA5sGkX7Pjjnfa".to_string(),
+            "14wj2RhTXzcCXuGLmWbDvbfPSBXXJCF9FqKhCEcDH4QKe".to_string(),
+            "17QBaVRKLKGEv9V4F5jQJQBiDFsdkvyfLvEVJzs8B36Xt".to_string(),
+        ];
+        assert!(config.validate().is_ok());
+        // 4 addresses for a 2 group network
+        config.groups = Some(2);
+        assert!(config.validate().is_err());
+        config.groups = None;
+        // the address of group 3 configured for group 2
+        config.addresses.swap(2, 3);
+        assert!(config.validate().is_err());
+        config.addresses = vec![];
+        config.groups = Some(17);
+        assert!(config.validate().is_err());
+        config.groups = None;
+        config.share_target = Some(vec![0xff; 33]);
+        assert!(config.validate().is_err());
+        config.share_target = Some(vec![]);
+        assert!(config.validate().is_err());
+        config.share_target = Some(vec![0xff; 32]);
+        assert!(config.validate().is_ok());
+        config.chains = vec![(0, 15)];
+        assert!(config.validate().is_ok());
+        config.groups = Some(4);
+        assert!(config.validate().is_err());
+        config.chains = vec![(0, 3)];
+        assert!(config.validate().is_ok());
+        config.worker_num = super::max_workers() + 1;
+        assert!(config.validate().is_err());
+        config.rig = Some("rig-1".to_string());
+        assert_eq!(config.rig_name(), "rig-1");
+    }
 }
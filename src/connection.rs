@@ -1,12 +1,18 @@
 use super::frame::Frame;
 use crate::frame::Error;
+use crate::model::ClientMessage;
 use bytes::Buf;
 use hex;
 use std::io::{self, Cursor};
 use std::result::Result;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Largest frame body accepted from a peer, the length prefix comes from
+/// the network and is checked before the read buffer grows.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(Debug)]
 pub struct Writer {
@@ -53,6 +59,16 @@ pub struct Reader {
 impl Reader {
     pub async fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
         loop {
+            // A single read may carry several frames, drain the buffer first.
+            if let Some(frame) = self.parse_frame()? {
+                return Ok(Some(frame));
+            }
+            // Ensure the buffer has capacity
+            if self.buffer.len() == self.cursor {
+                // Grow the buffer
+                self.buffer.resize(self.cursor * 2, 0);
+            }
+
             // Read into the buffer, tracking the number of bytes read
             let n = self.stream.read(&mut self.buffer[self.cursor..]).await?;
             if 0 == n {
@@ -65,26 +81,28 @@ impl Reader {
                 // Update our cursor
                 self.cursor += n;
             }
+        }
+    }
 
-            // Bytes.from(body.length) ++ body
-            // total = 4 + body.length ++ body
-            let mut buf = Cursor::new(&self.buffer[..]);
-            let size = buf.get_u32() as usize;
-            let remain = buf.remaining();
-            if size <= remain {
-                let (left, right) = self.buffer.split_at(size + 4);
-                let data = left.into();
-                self.buffer = self.buffer.split_off(size + 4);
-                self.cursor = self.cursor - size - 4;
-                // Return the parsed frame to the caller.
-                return Ok(Some(Frame::Bulk(data)));
-            }
-            // Ensure the buffer has capacity
-            if self.buffer.len() == self.cursor {
-                // Grow the buffer
-                self.buffer.resize(self.cursor * 2, 0);
-            }
+    fn parse_frame(&mut self) -> Result<Option<Frame>, Error> {
+        if self.cursor < 4 {
+            return Ok(None);
         }
+        // Bytes.from(body.length) ++ body
+        // total = 4 + body.length ++ body
+        let mut buf = Cursor::new(&self.buffer[..self.cursor]);
+        let size = buf.get_u32() as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(format!("frame of {} bytes exceeds {}", size, MAX_FRAME_SIZE).into());
+        }
+        if size > buf.remaining() {
+            return Ok(None);
+        }
+        let data = self.buffer[..size + 4].to_vec();
+        self.buffer.copy_within(size + 4..self.cursor, 0);
+        self.cursor -= size + 4;
+        // Return the parsed frame to the caller.
+        Ok(Some(Frame::Bulk(data)))
     }
 }
 
@@ -103,3 +121,95 @@ pub fn pair(stream: TcpStream) -> (Reader, Writer) {
         },
     )
 }
+
+/// Events of the miners connected to a server (proxy, mock node), each miner
+/// is identified by the order it connected in, starting from 1.
+pub enum Peer {
+    Connected(u32, Writer),
+    Request(u32, ClientMessage),
+    Disconnected(u32),
+}
+
+/// Accepts miner connections on `listener` and reports them to `tx` until
+/// the receiving side is dropped.
+pub fn serve(listener: TcpListener, tx: mpsc::Sender<Peer>) {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    tokio::spawn(async move {
+        let mut peer_id: u32 = 0;
+        loop {
+            let (stream, address) = match listener.accept().await {
+                Ok(val) => val,
+                Err(err) => {
+                    error!("accept error {}", err);
+                    continue;
+                }
+            };
+            peer_id += 1;
+            let id = peer_id;
+            info!("miner {} connected from {}", id, address);
+            let (mut r, w) = pair(stream);
+            if tx.send(Peer::Connected(id, w)).await.is_err() {
+                break;
+            }
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    match r.read_frame().await {
+                        Ok(Some(Frame::Bulk(bytes))) => {
+                            match bincode::decode_from_slice::<ClientMessage, _>(
+                                bytes.as_ref(),
+                                option,
+                            ) {
+                                Ok((req, _)) => {
+                                    if tx.send(Peer::Request(id, req)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                                Err(err) => error!("decode miner {} message error {:?}", id, err),
+                            }
+                        }
+                        Ok(Some(_)) => {}
+                        Ok(None) => break,
+                        Err(err) => {
+                            error!("miner {} read_frame error {}", id, err);
+                            break;
+                        }
+                    }
+                }
+                let _ = tx.send(Peer::Disconnected(id)).await;
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pair, MAX_FRAME_SIZE};
+    use crate::frame::Frame;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_frame_size() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut r, _w) = pair(stream);
+
+        client.write_all(&[0, 0, 0, 2, 7, 8]).await.unwrap();
+        match r.read_frame().await.unwrap() {
+            Some(Frame::Bulk(data)) => assert_eq!(data, vec![0, 0, 0, 2, 7, 8]),
+            _ => panic!("expected a frame"),
+        }
+        // only the length is sent, it is rejected before any body is read
+        let size = MAX_FRAME_SIZE as u32 + 1;
+        client.write_all(&size.to_be_bytes()).await.unwrap();
+        let err = r.read_frame().await.unwrap_err();
+        assert!(err.to_string().contains("exceeds"));
+    }
+}
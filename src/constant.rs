@@ -1,4 +1,9 @@
-pub const GROUP_NUMS: u32 = 4;
-pub const CHAIN_NUMS: u8 = 16;
+pub const GROUP_NUMS: u32 = 4; //主网分组数，未配置且尚未收到任务时使用
 pub const PARALLEL_MINING_WORKS: u32 = 16;
 pub const MINING_STEPS: u64 = 100000;
+pub const RECONNECT_DELAY: u64 = 5; //断线重连间隔，秒
+pub const RECONNECT_MAX_DELAY: u64 = 60; //代理重连节点的最长退避间隔，秒
+pub const MAX_PENDING_SUBMITS: usize = 1024; //等待节点结果的提交上限
+pub const MAX_WORKERS_PER_CPU: usize = 4; //工作线程数上限，按 CPU 数的倍数
+pub const PROGRESS_INTERVAL: u64 = 1; //汇总矿工计算进度的间隔，秒
+pub const WEBHOOK_TIMEOUT: u64 = 10; //出块通知请求超时，秒
@@ -0,0 +1,126 @@
+//! Runtime control of a running miner. Commands travel to the `Scheduler`,
+//! which owns the workers, and are answered with the resulting status.
+
+use crate::pow::HashImpl;
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+/// Json form: `{"command": "set_workers", "count": 4}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    Status,
+    Pause,
+    Resume,
+    SetWorkers {
+        count: usize,
+    },
+    AddWorkers {
+        count: usize,
+    },
+    RemoveWorkers {
+        count: usize,
+    },
+    //更换哈希实现时重建全部工作线程，节点连接不受影响
+    SetBackend {
+        miner_type: Option<String>,
+        hash_impl: Option<HashImpl>,
+    },
+    //断开当前节点连接并立即重连
+    Reconnect,
+    //重新读取配置文件
+    ReloadConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub paused: bool,
+    pub connected: bool,
+    pub miner_type: String,
+    pub hash_impl: HashImpl,
+    pub hash_rate: u64,
+    pub workers: Vec<WorkerStatus>,
+    pub jobs: Vec<JobStatus>, //最近一批任务
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    pub id: String,
+    pub state: String, //mining、idle、paused、suspended
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub from: u32,
+    pub to: u32,
+    pub target: String, //hex
+    pub difficulty: f64,
+    pub txs_size: usize,
+    pub age: u64, //收到后经过的秒数
+}
+
+pub type Reply = Result<Status, String>;
+
+pub type Request = (Command, oneshot::Sender<Reply>);
+
+/// Sending half, cheap to clone into every control frontend.
+#[derive(Clone)]
+pub struct Control {
+    tx: mpsc::Sender<Request>,
+}
+
+impl Control {
+    pub fn channel() -> (Control, mpsc::Receiver<Request>) {
+        let (tx, rx) = mpsc::channel(16);
+        (Control { tx }, rx)
+    }
+
+    pub async fn send(&self, command: Command) -> Reply {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.tx
+            .send((command, reply_tx))
+            .await
+            .map_err(|_| "miner is not running".to_string())?;
+        reply_rx
+            .await
+            .map_err(|_| "miner dropped the command".to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Control, Status};
+    use crate::pow::HashImpl;
+
+    #[tokio::test]
+    async fn test_control() {
+        let command: Command =
+            serde_json::from_str(r#"{"command": "set_workers", "count": 4}"#).unwrap();
+        assert_eq!(command, Command::SetWorkers { count: 4 });
+        let command: Command =
+            serde_json::from_str(r#"{"command": "set_backend", "hash_impl": "blake3-merkle"}"#)
+                .unwrap();
+        assert_eq!(
+            command,
+            Command::SetBackend {
+                miner_type: None,
+                hash_impl: Some(HashImpl::Blake3Merkle)
+            }
+        );
+
+        let command: Command = serde_json::from_str(r#"{"command": "reload_config"}"#).unwrap();
+        assert_eq!(command, Command::ReloadConfig);
+
+        let (control, mut rx) = Control::channel();
+        tokio::spawn(async move {
+            let (command, reply) = rx.recv().await.unwrap();
+            let _ = reply.send(Ok(Status {
+                paused: command == Command::Pause,
+                ..Default::default()
+            }));
+        });
+        assert!(control.send(Command::Pause).await.unwrap().paused);
+        // nobody left to answer
+        assert!(control.send(Command::Status).await.is_err());
+    }
+}
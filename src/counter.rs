@@ -1,25 +1,251 @@
+use crate::affinity::Placement;
+use crate::constant;
+use crate::model::Job;
+use crate::pow;
+use crate::rate::{HashRate, HashRates};
 use crate::task::Task;
-use std::collections::HashMap;
+use parking_lot::Mutex;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time;
 
+pub type SharedCounter = Arc<Mutex<Counter>>;
+
+//停止的工作线程最迟在当前检查点后上报一次，保留一分钟足够
+const RETIRED_TTL: time::Duration = time::Duration::from_secs(60);
+
 #[derive(Debug, Clone)]
 pub struct Counter {
-    tasks: HashMap<u64, Task>,
-    total_hash_count: u64,           //计算总次数
-    succeed_tasked_count: u64,       //已经完成的任务数
-    free_tasked_count: u64,          //释放掉的任务数
+    history: VecDeque<TaskSummary>, //最近完成的任务，超出 history_size 丢弃最旧的
+    history_size: usize,
+    task_count: u64,                      //完成的任务总数
+    total_hash_count: u64,                //计算总次数
+    succeed_tasked_count: u64,            //已经完成的任务数
+    free_tasked_count: u64,               //释放掉的任务数
+    share_count: u64,                     //提交的份额数
+    share_hash_count: f64,                //份额折算的计算次数
+    submitted_count: u64,                 //已发送给节点的区块和份额
+    accepted_count: u64,                  //节点接受的区块数
+    rejected_count: u64,                  //节点拒绝的区块数
+    shares_accepted: u64,                 //矿池接受的份额数
+    shares_rejected: u64,                 //矿池拒绝的份额数
+    connect_count: u64,                   //连接节点次数，重连数 = 连接次数 - 1
+    dropped_count: u64,                   //新任务到达时丢弃的排队任务
+    interrupted_count: u64,               //新任务到达时中断的计算任务
+    abandoned_count: u64,                 //超过计算上限主动放弃的任务
+    rejected_jobs: BTreeMap<String, u64>, //未通过检查的任务，按原因
+    job_latency: Summary,                 //任务到达至开始计算
+    submit_round_trip: Summary,           //提交至节点返回结果
+    backend: String,
+    rig: String,
+    addresses: Vec<String>, //各分组的收益地址
+    groups: u32,            //网络分组数
+    rate: HashRate,         //全部矿工的滑动窗口算力
+    workers: HashMap<String, WorkerCount>,
+    retired: HashMap<String, time::Instant>, //已停止的工作线程及停止时间，迟到的任务不再计入
+    chains: HashMap<(u32, u32), ChainCount>,
+    connection: Connection,
     miner_start_time: time::Instant, //每次计算任务的开始时间。
     print_setup_time: time::Instant,
     interval: u64,
 }
 
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// A finished task without its header and txs blobs.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskSummary {
+    pub task_id: u64,
+    pub worker_id: String,
+    pub from: u32,
+    pub to: u32,
+    pub status: usize,
+    pub hash_count: u64,
+    pub hash_rate: u64,
+    pub nonce: String, //hex
+}
+
+impl From<&Task> for TaskSummary {
+    fn from(task: &Task) -> Self {
+        TaskSummary {
+            task_id: task.task_id(),
+            worker_id: task.worker_id().to_string(),
+            from: task.job().from,
+            to: task.job().to,
+            status: task.status(),
+            hash_count: task.hash_count(),
+            hash_rate: task.hash_rate(),
+            nonce: hex::encode(task.nonce()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct WorkerCount {
+    hash_count: u64,
+    task_count: u64,
+    chain: Option<(u32, u32)>, //最近一次计算的链
+    rate: HashRate,
+    placement: Option<Placement>, //绑定的核
+}
+
+#[derive(Debug, Clone)]
+struct ChainCount {
+    received_time: time::Instant, //最新任务到达时间
+    difficulty: f64,
+    target: String, //hex
+    found: u64,
+    shares: u64,
+    accepted: u64,
+    rejected: u64,
+    shares_accepted: u64,
+    shares_rejected: u64,
+}
+
+impl Default for ChainCount {
+    fn default() -> Self {
+        ChainCount {
+            received_time: time::Instant::now(),
+            difficulty: 0f64,
+            target: "".to_string(),
+            found: 0,
+            shares: 0,
+            accepted: 0,
+            rejected: 0,
+            shares_accepted: 0,
+            shares_rejected: 0,
+        }
+    }
+}
+
+/// Sum and count of observed durations, in seconds.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Summary {
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Summary {
+    fn observe(&mut self, duration: time::Duration) {
+        self.sum += duration.as_secs_f64();
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Connection {
+    connected: bool,
+    endpoint: String,
+    since: time::Instant,
+}
+
+/// Snapshot of the counters, served by the stats api.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub uptime: u64,
+    pub backend: String,
+    pub rig: String,
+    pub addresses: Vec<String>,
+    pub groups: u32,
+    pub connection: ConnectionStats,
+    pub reconnects: u64,
+    pub hash_count: u64,
+    pub hash_rate: u64, //启动以来的平均算力
+    pub hash_rates: HashRates,
+    pub effective_hash_rate: u64,
+    pub found: u64,
+    pub shares: u64,
+    pub submitted: u64,
+    pub accepted: u64, //区块
+    pub rejected: u64,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    pub stale: StaleStats,
+    pub rejected_jobs: BTreeMap<String, u64>, //未通过检查的任务，按原因
+    pub job_latency: Summary,
+    pub submit_round_trip: Summary,
+    pub workers: Vec<WorkerStats>,
+    pub chains: Vec<ChainStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStats {
+    pub connected: bool,
+    pub endpoint: String,
+    pub since: u64, //当前状态持续的秒数
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleStats {
+    pub dropped: u64,
+    pub interrupted: u64,
+    pub abandoned: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStats {
+    pub id: String,
+    pub hash_count: u64,
+    pub hash_rate: u64,
+    pub hash_rates: HashRates,
+    pub tasks: u64,
+    pub chain: Option<(u32, u32)>,
+    pub core: Option<usize>,
+    pub numa_node: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainStats {
+    pub from: u32,
+    pub to: u32,
+    pub job_age: f64, //秒
+    pub difficulty: f64,
+    pub target: String,
+    pub found: u64,
+    pub shares: u64,
+    pub accepted: u64, //区块
+    pub rejected: u64,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+}
+
 impl Default for Counter {
     fn default() -> Self {
         Counter {
-            tasks: Default::default(),
+            history: VecDeque::with_capacity(DEFAULT_HISTORY_SIZE),
+            history_size: DEFAULT_HISTORY_SIZE,
+            task_count: 0,
             total_hash_count: 0,
             succeed_tasked_count: 0,
             free_tasked_count: 0,
+            share_count: 0,
+            share_hash_count: 0f64,
+            submitted_count: 0,
+            accepted_count: 0,
+            rejected_count: 0,
+            shares_accepted: 0,
+            shares_rejected: 0,
+            connect_count: 0,
+            dropped_count: 0,
+            interrupted_count: 0,
+            abandoned_count: 0,
+            rejected_jobs: Default::default(),
+            job_latency: Default::default(),
+            submit_round_trip: Default::default(),
+            backend: "cpu".to_string(),
+            rig: "".to_string(),
+            addresses: vec![],
+            groups: constant::GROUP_NUMS,
+            rate: Default::default(),
+            workers: Default::default(),
+            retired: Default::default(),
+            chains: Default::default(),
+            connection: Connection {
+                connected: false,
+                endpoint: "".to_string(),
+                since: time::Instant::now(),
+            },
             miner_start_time: time::Instant::now(),
             print_setup_time: time::Instant::now(),
             interval: 0,
@@ -36,26 +262,153 @@ impl Counter {
 
     pub fn add(&mut self, task: Task) {
         self.update_count(&task);
-        self.tasks.insert(task.task_id(), task);
+        //份额与所属任务同 id，不单独记录
+        if task.status() != 4 {
+            self.task_count += 1;
+            if self.history_size == 0 {
+                return;
+            }
+            if self.history.len() == self.history_size {
+                self.history.pop_front();
+            }
+            self.history.push_back((&task).into());
+        }
+    }
+
+    pub fn with_history_size(mut self, size: usize) -> Self {
+        self.history_size = size;
+        self.history = VecDeque::with_capacity(size);
+        self
+    }
+
+    /// Most recent finished tasks, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &TaskSummary> {
+        self.history.iter()
+    }
+
+    /// Up to `count` most recently found blocks, newest first.
+    pub fn recent_blocks(&self, count: usize) -> Vec<TaskSummary> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|task| task.status == 0)
+            .take(count)
+            .cloned()
+            .collect()
+    }
+
+    /// Seconds between the stats printed to the log.
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn set_interval(&mut self, interval: u64) {
+        self.interval = interval;
+    }
+
+    pub fn with_identity(mut self, rig: &str, addresses: Vec<String>) -> Self {
+        self.rig = rig.to_string();
+        self.addresses = addresses;
+        self
+    }
+
+    /// Group count learned from the node, chains of another network are
+    /// dropped from the stats.
+    pub fn set_groups(&mut self, groups: u32) {
+        self.groups = groups;
+        self.chains
+            .retain(|(from, to), _| *from < groups && *to < groups);
+    }
+
+    pub fn with_backend(mut self, backend: &str) -> Self {
+        self.backend = backend.to_string();
+        self
+    }
+
+    pub fn shared(self) -> SharedCounter {
+        Arc::new(Mutex::new(self))
     }
 
     fn update_count(&mut self, task: &Task) {
-        self.total_hash_count += task.hash_count();
+        //计算次数由矿工在检查点上报，见 record_hashes
+        if !self.retired.contains_key(task.worker_id()) {
+            let worker = self
+                .workers
+                .entry(task.worker_id().to_string())
+                .or_default();
+            worker.chain = Some((task.job().from, task.job().to));
+            if task.status() != 4 {
+                worker.task_count += 1;
+            }
+        }
+        if task.status() != 4 {
+            self.job_latency.observe(task.wait_time());
+        }
+        let chain = self
+            .chains
+            .entry((task.job().from, task.job().to))
+            .or_default();
         match task.status() {
             0 => {
                 self.succeed_tasked_count += 1;
+                chain.found += 1;
                 // self.succeed_tasks.push(task.clone());
             }
             1 => {
                 self.free_tasked_count += 1;
+                self.interrupted_count += 1;
             }
             2 => {
                 self.free_tasked_count += 1;
+                self.abandoned_count += 1;
+            }
+            4 => {
+                self.share_count += 1;
+                chain.shares += 1;
+                if let Some(target) = task.share_target() {
+                    self.share_hash_count += pow::share_hashes(target, task.groups());
+                }
             }
             _ => unreachable!(),
         }
     }
 
+    /// Counts hashes a worker reported since its last report, feeding the
+    /// lifetime totals and the sliding window rates.
+    pub fn record_hashes(&mut self, worker_id: &str, hashes: u64) {
+        let now = time::Instant::now();
+        self.total_hash_count += hashes;
+        self.rate.record(hashes, now);
+        if self.retired.contains_key(worker_id) {
+            return;
+        }
+        let worker = self.workers.entry(worker_id.to_string()).or_default();
+        worker.hash_count += hashes;
+        worker.rate.record(hashes, now);
+    }
+
+    /// Drops a stopped worker, its late reports only count towards totals.
+    pub fn remove_worker(&mut self, worker_id: &str) {
+        let now = time::Instant::now();
+        self.prune_retired(now);
+        self.workers.remove(worker_id);
+        self.retired.insert(worker_id.to_string(), now);
+    }
+
+    //停止超过 RETIRED_TTL 的线程不会再上报
+    fn prune_retired(&mut self, now: time::Instant) {
+        self.retired
+            .retain(|_, time| now.duration_since(*time) < RETIRED_TTL);
+    }
+
+    pub fn placed(&mut self, worker_id: &str, placement: Placement) {
+        self.workers
+            .entry(worker_id.to_string())
+            .or_default()
+            .placement = Some(placement);
+    }
+
     pub fn update_task_status(&mut self, task_id: u64, status: usize) {
         // self.tasks
         //     .get_mut(&task_id)
@@ -66,29 +419,271 @@ impl Counter {
         self.total_hash_count += count;
     }
 
+    pub fn job_received(&mut self, job: &Job) {
+        let chain = self.chains.entry((job.from, job.to)).or_default();
+        chain.received_time = time::Instant::now();
+        chain.difficulty = pow::difficulty(&job.target);
+        chain.target = hex::encode(&job.target);
+    }
+
+    //新任务到达时丢弃的排队任务数
+    pub fn dropped(&mut self, count: u64) {
+        self.dropped_count += count;
+    }
+
+    pub fn rejected_job(&mut self, reason: &str) {
+        *self.rejected_jobs.entry(reason.to_string()).or_default() += 1;
+    }
+
+    pub fn submitted(&mut self) {
+        self.submitted_count += 1;
+    }
+
+    /// Counts the node's answer to a block, or to a share in pool mode.
+    pub fn submit_result(&mut self, from: u32, to: u32, accepted: bool, share: bool) {
+        let chain = self.chains.entry((from, to)).or_default();
+        match (share, accepted) {
+            (false, true) => {
+                self.accepted_count += 1;
+                chain.accepted += 1;
+            }
+            (false, false) => {
+                self.rejected_count += 1;
+                chain.rejected += 1;
+            }
+            (true, true) => {
+                self.shares_accepted += 1;
+                chain.shares_accepted += 1;
+            }
+            (true, false) => {
+                self.shares_rejected += 1;
+                chain.shares_rejected += 1;
+            }
+        }
+    }
+
+    pub fn submit_round_trip(&mut self, duration: time::Duration) {
+        self.submit_round_trip.observe(duration);
+    }
+
+    pub fn connected(&mut self, endpoint: &str) {
+        self.connect_count += 1;
+        self.connection = Connection {
+            connected: true,
+            endpoint: endpoint.to_string(),
+            since: time::Instant::now(),
+        };
+    }
+
+    pub fn disconnected(&mut self) {
+        self.connection.connected = false;
+        self.connection.since = time::Instant::now();
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.connected
+    }
+
+    //不足一秒按一秒算
+    fn elapsed(&self) -> u64 {
+        (time::Instant::now() - self.miner_start_time)
+            .as_secs()
+            .max(1)
+    }
+
     pub fn hash_rate(&self) -> u64 {
-        let end_time = time::Instant::now();
-        self.total_hash_count / (end_time - self.miner_start_time).as_secs()
+        self.total_hash_count / self.elapsed()
+    }
+
+    /// Hash rate implied by the submitted shares, as the pool sees it.
+    pub fn effective_hash_rate(&self) -> u64 {
+        let elapsed = (time::Instant::now() - self.miner_start_time).as_secs_f64();
+        if elapsed > 0f64 {
+            (self.share_hash_count / elapsed) as u64
+        } else {
+            0
+        }
     }
 
     pub fn task_rate(&self) -> u64 {
-        let end_time = time::Instant::now();
-        self.tasks.len() as u64 / (end_time - self.miner_start_time).as_secs()
+        self.task_count / self.elapsed()
+    }
+
+    pub fn stats(&self) -> Stats {
+        let now = time::Instant::now();
+        let mut workers: Vec<WorkerStats> = self
+            .workers
+            .iter()
+            .map(|(id, worker)| WorkerStats {
+                id: id.clone(),
+                hash_count: worker.hash_count,
+                hash_rate: worker.hash_count / self.elapsed(),
+                hash_rates: worker.rate.rates(now),
+                tasks: worker.task_count,
+                chain: worker.chain,
+                core: worker.placement.map(|val| val.core),
+                numa_node: worker.placement.map(|val| val.node),
+            })
+            .collect();
+        workers.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut chains: Vec<ChainStats> = self
+            .chains
+            .iter()
+            .map(|((from, to), chain)| ChainStats {
+                from: *from,
+                to: *to,
+                job_age: (now - chain.received_time).as_secs_f64(),
+                difficulty: chain.difficulty,
+                target: chain.target.clone(),
+                found: chain.found,
+                shares: chain.shares,
+                accepted: chain.accepted,
+                rejected: chain.rejected,
+                shares_accepted: chain.shares_accepted,
+                shares_rejected: chain.shares_rejected,
+            })
+            .collect();
+        chains.sort_by_key(|chain| (chain.from, chain.to));
+        Stats {
+            uptime: (now - self.miner_start_time).as_secs(),
+            backend: self.backend.clone(),
+            rig: self.rig.clone(),
+            addresses: self.addresses.clone(),
+            groups: self.groups,
+            connection: ConnectionStats {
+                connected: self.connection.connected,
+                endpoint: self.connection.endpoint.clone(),
+                since: (now - self.connection.since).as_secs(),
+            },
+            reconnects: self.connect_count.saturating_sub(1),
+            hash_count: self.total_hash_count,
+            hash_rate: self.hash_rate(),
+            hash_rates: self.rate.rates(now),
+            effective_hash_rate: self.effective_hash_rate(),
+            found: self.succeed_tasked_count,
+            shares: self.share_count,
+            submitted: self.submitted_count,
+            accepted: self.accepted_count,
+            rejected: self.rejected_count,
+            shares_accepted: self.shares_accepted,
+            shares_rejected: self.shares_rejected,
+            stale: StaleStats {
+                dropped: self.dropped_count,
+                interrupted: self.interrupted_count,
+                abandoned: self.abandoned_count,
+            },
+            rejected_jobs: self.rejected_jobs.clone(),
+            job_latency: self.job_latency,
+            submit_round_trip: self.submit_round_trip,
+            workers,
+            chains,
+        }
     }
 
     pub fn interval_print(&mut self) {
         let now = time::Instant::now();
         if (now - self.print_setup_time).as_secs() > self.interval {
             self.print_setup_time = now;
+            let rates = self.rate.rates(now);
             info!(
-                "total hash count: {}, free_tasked_count: {} task count: {}, hash rate: {}, task rate:{}, ",
+                "rig {} total hash count: {}, free_tasked_count: {} task count: {}, share count: {}, hash rate: {}, 10s/1m/15m: {:.0}/{:.0}/{:.0}, effective hash rate: {}, task rate:{}, ",
+                self.rig,
                 self.total_hash_count,
                 // self.succeed_tasked_count,
                 self.free_tasked_count,
-                self.tasks.len(),
+                self.task_count,
+                self.share_count,
                 self.hash_rate(),
+                rates.s10,
+                rates.m1,
+                rates.m15,
+                self.effective_hash_rate(),
                 self.task_rate()
             );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Counter, RETIRED_TTL};
+    use crate::model::Job;
+    use crate::pow;
+    use crate::task::Task;
+    use std::time;
+
+    #[test]
+    fn test_history_bounded() {
+        let mut counter = Counter::new().with_history_size(100);
+        let capacity = counter.history.capacity();
+        let job = Job {
+            from: 1,
+            to: 2,
+            header: vec![1; 302],
+            txs: vec![2; 1024],
+            target: vec![0xff; 32],
+        };
+        for i in 0..1_000_000u64 {
+            let task = Task::new()
+                .with_job(job.clone())
+                .with_worker_id("worker".to_string())
+                .with_hash_count(i)
+                .with_status(if i % 1000 == 0 { 0 } else { 1 });
+            counter.add(task);
+        }
+        assert_eq!(counter.history().count(), 100);
+        assert_eq!(counter.history.capacity(), capacity);
+        assert_eq!(counter.history().last().unwrap().hash_count, 999_999);
+        let stats = counter.stats();
+        assert_eq!(stats.found, 1000);
+        assert_eq!(stats.workers[0].tasks, 1_000_000);
+    }
+
+    #[test]
+    fn test_share_rate() {
+        // every nonce is hashed, shares are the hashes meeting the share
+        // target on the job's chain, both counts cover the same time
+        let mut counter = Counter::new();
+        let job = Job {
+            from: 1,
+            to: 0,
+            header: vec![3; 302],
+            txs: vec![],
+            target: vec![0; 32],
+        };
+        let target = vec![0x0f; 32];
+        let task = Task::new()
+            .with_job(job.clone())
+            .with_share_target(Some(target.clone()))
+            .with_groups(2)
+            .with_worker_id("worker".to_string());
+        for index in 0..40_000u32 {
+            let mut nonce = [0u8; 24];
+            nonce[..4].copy_from_slice(&index.to_be_bytes());
+            counter.record_hashes("worker", pow::HASHES_PER_NONCE);
+            let hash = pow::hash(&nonce, &job.header);
+            if pow::check_target(&hash, &target) && pow::chain_index(&hash, 2) == (1, 0) {
+                counter.add(task.clone().with_nonce(nonce).with_status(4));
+            }
+        }
+        let ratio = counter.share_hash_count / counter.total_hash_count as f64;
+        assert!((ratio - 1f64).abs() < 0.1, "effective/local = {}", ratio);
+    }
+
+    #[test]
+    fn test_retired_pruned() {
+        let mut counter = Counter::new();
+        for index in 0..1000 {
+            let id = format!("worker-{}", index);
+            counter.record_hashes(&id, 10);
+            counter.remove_worker(&id);
+        }
+        // late reports of stopped workers only count towards totals
+        counter.record_hashes("worker-0", 10);
+        assert!(counter.workers.is_empty());
+        assert_eq!(counter.total_hash_count, 10010);
+        assert_eq!(counter.retired.len(), 1000);
+        counter.prune_retired(time::Instant::now() + RETIRED_TTL);
+        assert!(counter.retired.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+//! Local control socket for operators without http: one json
+//! `control::Command` per line in, one `control::Reply` per line out, and the
+//! `ctl` client talking to it.
+
+use crate::control::{Command, Control, Reply, Status};
+use crate::dashboard::human;
+use anyhow::{anyhow, bail};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Socket `ctl` connects to when neither `--socket` nor `control_socket`
+/// is set.
+pub const DEFAULT_SOCKET: &str = "/tmp/alephium-miner.sock";
+
+/// Commands of the `ctl` client.
+pub const COMMANDS: [&str; 8] = [
+    "status",
+    "workers",
+    "jobs",
+    "pause",
+    "resume",
+    "set-threads",
+    "reconnect",
+    "reload-config",
+];
+
+/// Listens on `path`, only the owner may connect. A socket left behind by
+/// a previous run is replaced, one still served by another miner is not.
+pub async fn serve(path: &str, control: Control) -> anyhow::Result<()> {
+    if UnixStream::connect(path).await.is_ok() {
+        bail!("{} is in use by another miner", path);
+    }
+    match fs::remove_file(path) {
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle(stream, control.clone()));
+                }
+                Err(err) => error!("control socket accept error {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn handle(stream: UnixStream, control: Control) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => control.send(command).await,
+            Err(err) => Err(format!("invalid command: {}", err)),
+        };
+        let mut data = serde_json::to_vec(&reply).expect("encode reply error");
+        data.push(b'\n');
+        if write.write_all(&data).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Sends one command to the miner listening on `path`.
+pub async fn request(path: &str, command: &Command) -> anyhow::Result<Reply> {
+    let stream = UnixStream::connect(path)
+        .await
+        .map_err(|err| anyhow!("connect {} error: {}, is control_socket set?", path, err))?;
+    let (read, mut write) = stream.into_split();
+    let mut data = serde_json::to_vec(command)?;
+    data.push(b'\n');
+    write.write_all(&data).await?;
+    let line = BufReader::new(read)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("miner closed the connection"))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// The control command behind a `ctl` command, `arg` is its argument.
+pub fn parse(name: &str, arg: Option<&str>) -> anyhow::Result<Command> {
+    Ok(match name {
+        "status" | "workers" | "jobs" => Command::Status,
+        "pause" => Command::Pause,
+        "resume" => Command::Resume,
+        "set-threads" => {
+            let count = arg.ok_or_else(|| anyhow!("set-threads needs a thread count"))?;
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| anyhow!("thread count must be a number"))?;
+            Command::SetWorkers { count }
+        }
+        "reconnect" => Command::Reconnect,
+        "reload-config" => Command::ReloadConfig,
+        _ => bail!("unknown command {}", name),
+    })
+}
+
+/// The part of `status` the `name` command is about.
+pub fn describe(name: &str, status: &Status) -> String {
+    match name {
+        "workers" => {
+            let mut out = format!("{:<38} {}\n", "worker", "state");
+            for worker in &status.workers {
+                out.push_str(&format!("{:<38} {}\n", worker.id, worker.state));
+            }
+            out
+        }
+        "jobs" => {
+            let mut out = format!(
+                "{:<6} {:>12} {:>10} {:>6}\n",
+                "chain", "difficulty", "txs", "age"
+            );
+            for job in &status.jobs {
+                out.push_str(&format!(
+                    "{:<6} {:>12} {:>10} {:>5}s\n",
+                    format!("{}->{}", job.from, job.to),
+                    human(job.difficulty),
+                    job.txs_size,
+                    job.age
+                ));
+            }
+            out
+        }
+        _ => {
+            let mining = status
+                .workers
+                .iter()
+                .filter(|val| val.state == "mining")
+                .count();
+            format!(
+                "state:     {}\nnode:      {}\nbackend:   {} ({})\nhash rate: {} hash/s\nworkers:   {} ({} mining)\njobs:      {}\n",
+                if status.paused { "paused" } else { "running" },
+                if status.connected {
+                    "connected"
+                } else {
+                    "disconnected"
+                },
+                status.miner_type,
+                status.hash_impl,
+                human(status.hash_rate as f64),
+                status.workers.len(),
+                mining,
+                status.jobs.len()
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, parse, request, serve};
+    use crate::control::{Command, Control, Status, WorkerStatus};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("jobs", None).unwrap(), Command::Status);
+        assert_eq!(
+            parse("set-threads", Some("4")).unwrap(),
+            Command::SetWorkers { count: 4 }
+        );
+        assert!(parse("set-threads", None).is_err());
+        assert!(parse("set-threads", Some("four")).is_err());
+        assert!(parse("stop", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_socket() {
+        let path = std::env::temp_dir().join(format!("miner-{}.sock", rand::random::<u32>()));
+        let path = path.to_str().unwrap();
+        let (control, mut rx) = Control::channel();
+        tokio::spawn(async move {
+            while let Some((command, reply)) = rx.recv().await {
+                let ret = match command {
+                    Command::Reconnect => Err("not connected".to_string()),
+                    command => Ok(Status {
+                        paused: command == Command::Pause,
+                        workers: vec![WorkerStatus {
+                            id: "w1".to_string(),
+                            state: "paused".to_string(),
+                        }],
+                        ..Default::default()
+                    }),
+                };
+                let _ = reply.send(ret);
+            }
+        });
+        serve(path, control.clone()).await.unwrap();
+        // a running miner keeps its socket
+        assert!(serve(path, control).await.is_err());
+
+        let status = request(path, &Command::Pause).await.unwrap().unwrap();
+        assert!(status.paused);
+        assert!(describe("status", &status).contains("state:     paused"));
+        assert!(describe("workers", &status).contains("w1"));
+        assert_eq!(
+            request(path, &Command::Reconnect).await.unwrap(),
+            Err("not connected".to_string())
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+use crate::counter::{SharedCounter, Stats, TaskSummary};
+use crate::worker::SharedNotifiers;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::Spans;
+use tui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use tui::{Frame, Terminal};
+
+const REFRESH: Duration = Duration::from_secs(1);
+const RECENT_BLOCKS: usize = 5;
+
+/// Terminal dashboard of the `Counter` stats, `--tui`. Workers are paused and
+/// resumed from the keyboard through their `Notifier`.
+pub struct Dashboard {
+    counter: SharedCounter,
+    notifiers: SharedNotifiers,
+    selected: usize,
+}
+
+//一次刷新的数据
+struct View {
+    stats: Stats,
+    workers: Vec<WorkerView>,
+    blocks: Vec<TaskSummary>,
+    selected: usize,
+}
+
+struct WorkerView {
+    id: String,
+    state: &'static str,
+    task_hashes: u64,
+}
+
+impl Dashboard {
+    pub fn new(counter: SharedCounter, notifiers: SharedNotifiers) -> Dashboard {
+        Dashboard {
+            counter,
+            notifiers,
+            selected: 0,
+        }
+    }
+
+    /// Draws until `q` or ctrl-c, blocking the calling thread.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        let ret = self.event_loop(&mut terminal);
+        terminal::disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        ret
+    }
+
+    fn event_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> anyhow::Result<()> {
+        loop {
+            let view = self.view();
+            terminal.draw(|f| draw(f, &view))?;
+            if !event::poll(REFRESH)? {
+                continue;
+            }
+            if let Event::Key(KeyEvent { code, modifiers }) = event::read()? {
+                //工作线程可能在运行时增减
+                let notifiers = self.notifiers.read().clone();
+                match code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                    KeyCode::Down => {
+                        self.selected = (self.selected + 1).min(notifiers.len().max(1) - 1)
+                    }
+                    KeyCode::Char('p') | KeyCode::Char(' ') => {
+                        if let Some(notifier) = notifiers.get(self.selected) {
+                            if notifier.is_paused() {
+                                notifier.resume();
+                            } else {
+                                notifier.pause();
+                            }
+                        }
+                    }
+                    KeyCode::Char('a') => notifiers.iter().for_each(|val| val.pause()),
+                    KeyCode::Char('r') => notifiers.iter().for_each(|val| val.resume()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn view(&self) -> View {
+        let (stats, blocks) = {
+            let counter = self.counter.lock();
+            (counter.stats(), counter.recent_blocks(RECENT_BLOCKS))
+        };
+        let workers = self
+            .notifiers
+            .read()
+            .iter()
+            .map(|notifier| WorkerView {
+                id: notifier.worker_id().to_string(),
+                state: notifier.state(),
+                task_hashes: notifier.task_hashes(),
+            })
+            .collect();
+        View {
+            stats,
+            workers,
+            blocks,
+            selected: self.selected,
+        }
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, view: &View) {
+    let stats = &view.stats;
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(5),
+            Constraint::Min(6),
+            Constraint::Length(stats.chains.len() as u16 + 3),
+            Constraint::Length(RECENT_BLOCKS as u16 + 3),
+            Constraint::Length(1),
+        ])
+        .split(f.size());
+
+    let connection = if stats.connection.connected {
+        format!(
+            "connected to {} for {}s",
+            stats.connection.endpoint, stats.connection.since
+        )
+    } else {
+        format!("disconnected for {}s", stats.connection.since)
+    };
+    let rates = stats.hash_rates;
+    let summary = Paragraph::new(vec![
+        Spans::from(format!(
+            "{}, reconnects: {}, uptime: {}s",
+            connection, stats.reconnects, stats.uptime
+        )),
+        Spans::from(format!(
+            "hash rate 10s/1m/15m: {} / {} / {}  ({})",
+            human(rates.s10),
+            human(rates.m1),
+            human(rates.m15),
+            stats.backend
+        )),
+        Spans::from(format!(
+            "found: {}  accepted: {}  rejected: {}  shares: {} ({} accepted, {} rejected)  submitted: {}",
+            stats.found,
+            stats.accepted,
+            stats.rejected,
+            stats.shares,
+            stats.shares_accepted,
+            stats.shares_rejected,
+            stats.submitted
+        )),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("miner {}", stats.rig).trim_end().to_string()),
+    );
+    f.render_widget(summary, areas[0]);
+
+    let rows = view.workers.iter().enumerate().map(|(index, worker)| {
+        let worker_stats = stats.workers.iter().find(|val| val.id == worker.id);
+        let chain = worker_stats
+            .and_then(|val| val.chain)
+            .map_or("-".to_string(), |(from, to)| format!("{}-{}", from, to));
+        let rates = worker_stats.map(|val| val.hash_rates).unwrap_or_default();
+        Row::new(vec![
+            index.to_string(),
+            worker.id.chars().take(8).collect(),
+            worker.state.to_string(),
+            chain,
+            human(rates.s10),
+            human(rates.m1),
+            human(worker.task_hashes as f64),
+        ])
+    });
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(12),
+    ];
+    let workers = Table::new(rows)
+        .header(
+            Row::new(vec![
+                "#",
+                "id",
+                "state",
+                "chain",
+                "10s",
+                "1m",
+                "task hashes",
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .widths(&widths)
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(Block::default().borders(Borders::ALL).title("workers"));
+    let mut state = TableState::default();
+    state.select(Some(view.selected));
+    f.render_stateful_widget(workers, areas[1], &mut state);
+
+    let rows = stats.chains.iter().map(|chain| {
+        Row::new(vec![
+            format!("{}-{}", chain.from, chain.to),
+            format!("{:.1}s", chain.job_age),
+            human(chain.difficulty),
+            chain.found.to_string(),
+            chain.accepted.to_string(),
+            chain.rejected.to_string(),
+            chain.target.clone(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Min(10),
+    ];
+    let chains = Table::new(rows)
+        .header(
+            Row::new(vec![
+                "chain",
+                "job age",
+                "difficulty",
+                "found",
+                "accepted",
+                "rejected",
+                "target",
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .widths(&widths)
+        .block(Block::default().borders(Borders::ALL).title("chains"));
+    f.render_widget(chains, areas[2]);
+
+    let rows = view.blocks.iter().map(|block| {
+        Row::new(vec![
+            format!("{}-{}", block.from, block.to),
+            block.worker_id.chars().take(8).collect(),
+            human(block.hash_count as f64),
+            block.nonce.clone(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(10),
+        Constraint::Min(48),
+    ];
+    let blocks = Table::new(rows)
+        .header(
+            Row::new(vec!["chain", "worker", "hashes", "nonce"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .widths(&widths)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("recent blocks"),
+        );
+    f.render_widget(blocks, areas[3]);
+
+    let help = Paragraph::new("↑/↓ select  p pause/resume  a pause all  r resume all  q quit");
+    f.render_widget(help, areas[4]);
+}
+
+//1234567 => 1.23M
+pub fn human(val: f64) -> String {
+    let units = ["", "K", "M", "G", "T", "P"];
+    let mut val = val;
+    let mut unit = 0;
+    while val >= 1000f64 && unit < units.len() - 1 {
+        val /= 1000f64;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0}", val)
+    } else {
+        format!("{:.2}{}", val, units[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw, human, View, WorkerView};
+    use crate::counter::Counter;
+    use crate::model::Job;
+    use tui::backend::TestBackend;
+    use tui::Terminal;
+
+    #[test]
+    fn test_draw() {
+        assert_eq!(human(999f64), "999");
+        assert_eq!(human(1234567f64), "1.23M");
+
+        let mut counter = Counter::new().with_backend("cpu");
+        counter.connected("127.0.0.1:10973");
+        counter.job_received(&Job {
+            from: 3,
+            to: 1,
+            target: vec![0xff; 31],
+            ..Default::default()
+        });
+        counter.record_hashes("0123456789", 5000);
+        let view = View {
+            stats: counter.stats(),
+            workers: vec![WorkerView {
+                id: "0123456789".to_string(),
+                state: "paused",
+                task_hashes: 2000,
+            }],
+            blocks: vec![],
+            selected: 0,
+        };
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|f| draw(f, &view)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol.clone())
+            .collect();
+        assert!(screen.contains("connected to 127.0.0.1:10973"));
+        assert!(screen.contains("01234567"));
+        assert!(screen.contains("paused"));
+        assert!(screen.contains("2.00K"));
+        assert!(screen.contains("3-1"));
+    }
+}
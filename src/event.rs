@@ -0,0 +1,136 @@
+//! Structured miner events. They are logged as `event key=value ...` lines,
+//! or as one json object per line with `--log-format json`.
+
+use serde_derive::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Hashes, nonces and targets are hex encoded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    JobReceived {
+        from: u32,
+        to: u32,
+        target: String,
+        difficulty: f64,
+        txs_size: usize,
+    },
+    TaskStarted {
+        worker: String,
+        task_id: u64,
+        from: u32,
+        to: u32,
+    },
+    SolutionFound {
+        worker: String,
+        task_id: u64,
+        from: u32,
+        to: u32,
+        nonce: String,
+        hash: String,
+        share: bool,
+    },
+    SubmitSent {
+        from: u32,
+        to: u32,
+        nonce: String,
+        share: bool,
+    },
+    SubmitResult {
+        from: u32,
+        to: u32,
+        accepted: bool,
+        round_trip: Option<f64>, //秒
+    },
+    Reconnect {
+        endpoint: String,
+        connected: bool,
+        error: Option<String>,
+    },
+}
+
+impl Event {
+    pub fn emit(&self) {
+        let level = match self {
+            Event::Reconnect { error: Some(_), .. } => log::Level::Warn,
+            _ => log::Level::Info,
+        };
+        if is_json() {
+            let mut value = serde_json::to_value(self).expect("serialize event error");
+            if let Some(object) = value.as_object_mut() {
+                object.insert("ts".to_string(), chrono::Utc::now().to_rfc3339().into());
+                object.insert("level".to_string(), level.as_str().into());
+            }
+            log!(level, "{}", value);
+        } else {
+            log!(level, "{}", self);
+        }
+    }
+}
+
+//event key=value ...
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = serde_json::to_value(self).map_err(|_| fmt::Error)?;
+        let object = value.as_object().ok_or(fmt::Error)?;
+        if let Some(event) = object.get("event").and_then(|val| val.as_str()) {
+            write!(f, "{}", event)?;
+        }
+        for (key, val) in object.iter().filter(|(key, _)| *key != "event") {
+            match val {
+                serde_json::Value::String(val) => write!(f, " {}={}", key, val)?,
+                serde_json::Value::Null => {}
+                val => write!(f, " {}={}", key, val)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+
+    #[test]
+    fn test_event() {
+        let event = Event::SolutionFound {
+            worker: "w1".to_string(),
+            task_id: 7,
+            from: 1,
+            to: 2,
+            nonce: "00ff".to_string(),
+            hash: "abcd".to_string(),
+            share: false,
+        };
+        let text = event.to_string();
+        assert!(text.starts_with("solution_found "));
+        assert!(text.contains(" nonce=00ff"));
+        assert!(text.contains(" share=false"));
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "solution_found");
+        assert_eq!(json["from"], 1);
+        assert_eq!(json["hash"], "abcd");
+
+        let event = Event::Reconnect {
+            endpoint: "127.0.0.1:10973".to_string(),
+            connected: true,
+            error: None,
+        };
+        assert_eq!(
+            event.to_string(),
+            "reconnect connected=true endpoint=127.0.0.1:10973"
+        );
+    }
+}
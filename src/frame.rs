@@ -71,8 +71,8 @@ impl From<TryFromIntError> for Error {
 }
 
 impl From<std::io::Error> for Error {
-    fn from(_src: std::io::Error) -> Error {
-        _src.into()
+    fn from(src: std::io::Error) -> Error {
+        src.to_string().into()
     }
 }
 
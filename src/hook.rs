@@ -0,0 +1,168 @@
+use crate::config::NotifyConfig;
+use crate::task::Task;
+use crate::{constant, http, pow};
+use serde_derive::Serialize;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// A block found by this miner together with the node's answer.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockEvent {
+    pub from: u32,
+    pub to: u32,
+    pub hash: String,
+    pub nonce: String,
+    pub accepted: bool,
+    pub timestamp: i64, //毫秒
+}
+
+impl BlockEvent {
+    pub fn new(task: &Task, accepted: bool) -> BlockEvent {
+        let job = task.job();
+        BlockEvent {
+            from: job.from,
+            to: job.to,
+            hash: hex::encode(pow::hash(task.nonce(), &job.header)),
+            nonce: hex::encode(task.nonce()),
+            accepted,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+//出块通知：命令、JSON lines 文件、webhook
+#[derive(Debug, Clone, Default)]
+pub struct Hook {
+    conf: NotifyConfig,
+}
+
+impl Hook {
+    pub fn new(conf: NotifyConfig) -> Hook {
+        Hook { conf }
+    }
+
+    /// Reports the event in the background, failures are only logged.
+    pub fn fire(&self, event: BlockEvent) {
+        let conf = self.conf.clone();
+        tokio::spawn(async move { dispatch(&conf, &event).await });
+    }
+}
+
+async fn dispatch(conf: &NotifyConfig, event: &BlockEvent) {
+    let json = serde_json::to_string(event).expect("serialize block event error");
+    if let Some(command) = conf.command.as_ref() {
+        if let Err(err) = run_command(command, event, &json).await {
+            error!("block notify command error: {}", err);
+        }
+    }
+    if let Some(path) = conf.file.as_ref() {
+        if let Err(err) = append_file(path, &json).await {
+            error!("block notify file {} error: {}", path, err);
+        }
+    }
+    if let Some(url) = conf.webhook.as_ref() {
+        if let Err(err) = post_webhook(url, &json).await {
+            error!("block notify webhook {} error: {}", url, err);
+        }
+    }
+}
+
+async fn run_command(command: &str, event: &BlockEvent, json: &str) -> anyhow::Result<()> {
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("BLOCK_FROM", event.from.to_string())
+        .env("BLOCK_TO", event.to.to_string())
+        .env("BLOCK_HASH", &event.hash)
+        .env("BLOCK_NONCE", &event.nonce)
+        .env("BLOCK_ACCEPTED", event.accepted.to_string())
+        .env("BLOCK_JSON", json)
+        .status()
+        .await?;
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", command, status);
+    }
+    Ok(())
+}
+
+async fn append_file(path: &str, json: &str) -> anyhow::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(format!("{}\n", json).as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+async fn post_webhook(url: &str, json: &str) -> anyhow::Result<()> {
+    let timeout = Duration::from_secs(constant::WEBHOOK_TIMEOUT);
+    let status = http::post_json(url, json, timeout).await?;
+    if !(200..300).contains(&status) {
+        anyhow::bail!("http status {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, BlockEvent};
+    use crate::config::NotifyConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            // the client only closes its side after reading the response
+            while !String::from_utf8_lossy(&request).contains("}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let dir = std::env::temp_dir().join(format!("hook-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("blocks.jsonl");
+        let env = dir.join("env");
+        let conf = NotifyConfig {
+            command: Some(format!(
+                "echo $BLOCK_FROM-$BLOCK_TO $BLOCK_ACCEPTED > {}",
+                env.display()
+            )),
+            file: Some(file.display().to_string()),
+            webhook: Some(format!("http://{}/block", address)),
+        };
+        let event = BlockEvent {
+            from: 2,
+            to: 3,
+            hash: "00aa".to_string(),
+            nonce: "01".to_string(),
+            accepted: true,
+            timestamp: 0,
+        };
+        dispatch(&conf, &event).await;
+
+        assert_eq!(std::fs::read_to_string(&env).unwrap(), "2-3 true\n");
+        let lines = std::fs::read_to_string(&file).unwrap();
+        let json: serde_json::Value = serde_json::from_str(lines.trim_end()).unwrap();
+        assert_eq!(json["hash"], "00aa");
+        assert_eq!(json["accepted"], true);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /block HTTP/1.1\r\n"));
+        assert!(request.ends_with(&serde_json::to_string(&event).unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+//! Just enough HTTP/1.1 for webhooks and the local api, plain http only and
+//! one request per connection.
+
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10); //读请求、处理并写回响应的时限
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json<T: Serialize>(val: &T) -> Response {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body: serde_json::to_string(val).expect("serialize response error"),
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.to_string(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Answers every request accepted on `listener` with `handler`.
+pub fn serve<F, R>(listener: TcpListener, handler: F)
+where
+    F: Fn(Request) -> R + Send + Sync + 'static,
+    R: Future<Output = Response> + Send,
+{
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(val) => val,
+                Err(err) => {
+                    error!("http accept error {}", err);
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let handled = handle(stream, handler.as_ref());
+                match tokio::time::timeout(REQUEST_TIMEOUT, handled).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => debug!("http connection error {}", err),
+                    Err(_) => debug!("http connection timed out"),
+                }
+            });
+        }
+    });
+}
+
+async fn handle<F: Fn(Request) -> R, R: Future<Output = Response>>(
+    mut stream: TcpStream,
+    handler: &F,
+) -> anyhow::Result<()> {
+    let mut buf = vec![];
+    let mut chunk = [0u8; 1024];
+    let head_end = loop {
+        if let Some(index) = buf.windows(4).position(|val| val == b"\r\n\r\n") {
+            break index;
+        }
+        if buf.len() > MAX_HEAD_SIZE {
+            anyhow::bail!("request head too large");
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let path = request_line.next().unwrap_or("/").to_string();
+    let length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, val)| val.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_SIZE {
+        let response = Response::text(413, "request body too large");
+        return respond(&mut stream, response).await;
+    }
+    let mut body = buf[head_end + 4..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed");
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    let response = handler(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+    .await;
+    respond(&mut stream, response).await
+}
+
+async fn respond(stream: &mut TcpStream, response: Response) -> anyhow::Result<()> {
+    let data = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(data.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Splits `http://host[:port][/path]` into host, socket address and path.
+fn parse_url(url: &str) -> anyhow::Result<(String, String, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("only http:// urls are supported: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    Ok((authority.to_string(), address, path.to_string()))
+}
+
+/// POSTs a JSON body and returns the response status code, gives up when
+/// the whole exchange takes longer than `timeout`.
+pub async fn post_json(url: &str, body: &str, timeout: Duration) -> anyhow::Result<u16> {
+    tokio::time::timeout(timeout, post(url, body))
+        .await
+        .map_err(|_| anyhow::anyhow!("{} timed out after {:?}", url, timeout))?
+}
+
+async fn post(url: &str, body: &str) -> anyhow::Result<u16> {
+    let (host, address, path) = parse_url(url)?;
+    let mut stream = TcpStream::connect(address).await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow::anyhow!("invalid http response from {}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_url, post_json, serve, Response, MAX_BODY_SIZE};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        serve(listener, |req| async move {
+            if req.method == "POST" && req.path == "/echo" && req.body == "{}" {
+                Response::text(200, "ok")
+            } else {
+                Response::text(404, "not found")
+            }
+        });
+        let timeout = Duration::from_secs(5);
+        let status = post_json(&format!("http://{}/echo", address), "{}", timeout).await;
+        assert_eq!(status.unwrap(), 200);
+        let status = post_json(&format!("http://{}/other", address), "{}", timeout).await;
+        assert_eq!(status.unwrap(), 404);
+
+        // a huge length is answered right away, nothing is read or allocated
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "POST /echo HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+
+    #[tokio::test]
+    async fn test_post_timeout() {
+        // accepts the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let timeout = Duration::from_millis(100);
+        let status = post_json(&format!("http://{}/hook", address), "{}", timeout).await;
+        assert!(status.unwrap_err().to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn test_parse_url() {
+        let (host, address, path) = parse_url("http://127.0.0.1:8080/hook/block").unwrap();
+        assert_eq!(host, "127.0.0.1:8080");
+        assert_eq!(address, "127.0.0.1:8080");
+        assert_eq!(path, "/hook/block");
+
+        let (_, address, path) = parse_url("http://example.com").unwrap();
+        assert_eq!(address, "example.com:80");
+        assert_eq!(path, "/");
+
+        assert!(parse_url("https://example.com/").is_err());
+    }
+}
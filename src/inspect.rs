@@ -0,0 +1,385 @@
+//! `decode` / `encode` subcommands: protocol messages to readable text or
+//! json, and json back to wire bytes for crafting test vectors.
+
+use crate::model::{Body, ClientMessage, Hello, Job, Message, SubmitReq, SubmitResult};
+use crate::{pow, serder};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::io::Read;
+
+/// Json form of a message, blobs are hex strings:
+/// `{"jobs": [{"from": 0, "to": 1, "header": "..", "txs": "..", "target": ".."}]}`,
+/// `{"submit_req": {"nonce": "..", "header": "..", "txs": ".."}}`,
+/// `{"submit_result": {"from": 0, "to": 1, "status": true}}`,
+/// `{"nonce_prefix": 7}`, `{"hello": {"rig": "..", "addresses": [".."]}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wire {
+    Jobs(Vec<JobJson>),
+    SubmitReq(SubmitReqJson),
+    SubmitResult(SubmitResultJson),
+    NoncePrefix(u32),
+    Hello(HelloJson),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobJson {
+    pub from: u32,
+    pub to: u32,
+    #[serde(with = "serder::hex_bytes")]
+    pub header: Vec<u8>,
+    #[serde(with = "serder::hex_bytes")]
+    pub txs: Vec<u8>,
+    #[serde(with = "serder::hex_bytes")]
+    pub target: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmitReqJson {
+    #[serde(with = "serder::hex_bytes")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "serder::hex_bytes")]
+    pub header: Vec<u8>,
+    #[serde(with = "serder::hex_bytes")]
+    pub txs: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmitResultJson {
+    pub from: u32,
+    pub to: u32,
+    pub status: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HelloJson {
+    pub rig: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+impl From<Body> for Wire {
+    fn from(body: Body) -> Self {
+        match body {
+            Body::Jobs(jobs) => Wire::Jobs(
+                jobs.into_iter()
+                    .map(|job| JobJson {
+                        from: job.from,
+                        to: job.to,
+                        header: job.header,
+                        txs: job.txs,
+                        target: job.target,
+                    })
+                    .collect(),
+            ),
+            Body::SubmitReq(req) => Wire::SubmitReq(SubmitReqJson {
+                nonce: req.nonce,
+                header: req.header,
+                txs: req.txs,
+            }),
+            Body::SubmitResult(ret) => Wire::SubmitResult(SubmitResultJson {
+                from: ret.from,
+                to: ret.to,
+                status: ret.status,
+            }),
+            Body::NoncePrefix(prefix) => Wire::NoncePrefix(prefix),
+            Body::Hello(hello) => Wire::Hello(HelloJson {
+                rig: hello.rig,
+                addresses: hello.addresses,
+            }),
+        }
+    }
+}
+
+impl From<Wire> for Message {
+    fn from(wire: Wire) -> Self {
+        match wire {
+            Wire::Jobs(jobs) => Message::jobs(
+                jobs.into_iter()
+                    .map(|job| Job {
+                        from: job.from,
+                        to: job.to,
+                        header: job.header,
+                        txs: job.txs,
+                        target: job.target,
+                    })
+                    .collect(),
+            ),
+            Wire::SubmitReq(req) => Message::submit_req(SubmitReq {
+                nonce: req.nonce,
+                header: req.header,
+                txs: req.txs,
+            }),
+            Wire::SubmitResult(ret) => Message::submit_result(SubmitResult {
+                from: ret.from,
+                to: ret.to,
+                status: ret.status,
+            }),
+            Wire::NoncePrefix(prefix) => Message::nonce_prefix(prefix),
+            Wire::Hello(hello) => Message::hello(Hello {
+                rig: hello.rig,
+                addresses: hello.addresses,
+            }),
+        }
+    }
+}
+
+/// Splits `data` into size prefixed messages and decodes each. Kind 0 is
+/// both jobs (from the node) and a submission (from a miner): jobs are tried
+/// first, a submission if they do not consume the whole message.
+pub fn decode(data: &[u8]) -> anyhow::Result<Vec<Wire>> {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    let mut messages = vec![];
+    let mut offset = 0;
+    while offset < data.len() {
+        if data.len() - offset < 5 {
+            anyhow::bail!("truncated message at byte {}", offset);
+        }
+        let mut size = [0u8; 4];
+        size.copy_from_slice(&data[offset..offset + 4]);
+        let end = offset + 4 + u32::from_be_bytes(size) as usize;
+        if end > data.len() {
+            anyhow::bail!(
+                "message at byte {} needs {} bytes, {} left",
+                offset,
+                end - offset,
+                data.len() - offset
+            );
+        }
+        let frame = &data[offset..end];
+        let wire = match bincode::decode_from_slice::<Message, _>(frame, option) {
+            Ok((msg, size)) if size == frame.len() => Body::from(msg).into(),
+            _ => match bincode::decode_from_slice::<ClientMessage, _>(frame, option) {
+                Ok((ClientMessage::SubmitReq(req), _)) => Body::SubmitReq(req).into(),
+                Ok((ClientMessage::Hello(hello), _)) => Body::Hello(hello).into(),
+                Err(err) => anyhow::bail!("decode message at byte {} error {:?}", offset, err),
+            },
+        };
+        messages.push(wire);
+        offset = end;
+    }
+    Ok(messages)
+}
+
+pub fn encode(messages: Vec<Wire>) -> Vec<u8> {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    let mut data = vec![];
+    for wire in messages {
+        let msg: Message = wire.into();
+        data.extend(bincode::encode_to_vec(msg, option).expect("encode_to_vec msg error"));
+    }
+    data
+}
+
+/// Json input of `encode`: one message or an array of them.
+pub fn parse_json(input: &str) -> anyhow::Result<Vec<Wire>> {
+    let value: serde_json::Value = serde_json::from_str(input)?;
+    Ok(match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value)?,
+        _ => vec![serde_json::from_value(value)?],
+    })
+}
+
+/// Hex input of `decode`, whitespace and a `0x` prefix are ignored.
+pub fn parse_hex(input: &str) -> anyhow::Result<Vec<u8>> {
+    let input: String = input.split_whitespace().collect();
+    Ok(hex::decode(input.trim_start_matches("0x"))?)
+}
+
+pub fn read_stdin() -> String {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .expect("read stdin error");
+    input
+}
+
+/// Fields of a mining header blob: the block header without its nonce.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub version: u8,
+    pub deps: Vec<Vec<u8>>,
+    pub dep_state_hash: Vec<u8>,
+    pub txs_hash: Vec<u8>,
+    pub timestamp: u64, //毫秒
+    pub target: Vec<u8>,
+}
+
+//version(1) + 依赖数(1) + 依赖(32 * n) + depStateHash(32) + txsHash(32) + timestamp(8) + target
+pub fn parse_header(blob: &[u8]) -> Option<Header> {
+    let version = *blob.first()?;
+    let count = *blob.get(1)? as usize;
+    let mut offset = 2;
+    let mut take = |len: usize| {
+        let val = blob.get(offset..offset + len)?.to_vec();
+        offset += len;
+        Some(val)
+    };
+    let deps = (0..count).map(|_| take(32)).collect::<Option<Vec<_>>>()?;
+    let dep_state_hash = take(32)?;
+    let txs_hash = take(32)?;
+    let mut timestamp = [0u8; 8];
+    timestamp.copy_from_slice(&take(8)?);
+    let target = blob.get(offset..)?.to_vec();
+    if target.is_empty() {
+        return None;
+    }
+    Some(Header {
+        version,
+        deps,
+        dep_state_hash,
+        txs_hash,
+        timestamp: u64::from_be_bytes(timestamp),
+        target,
+    })
+}
+
+fn describe_header(out: &mut String, blob: &[u8]) {
+    match parse_header(blob) {
+        Some(header) => {
+            let time = chrono::DateTime::from_timestamp_millis(header.timestamp as i64)
+                .map_or("-".to_string(), |val| val.to_string());
+            writeln!(
+                out,
+                "    header: {} bytes, version {}",
+                blob.len(),
+                header.version
+            )
+            .unwrap();
+            for (index, dep) in header.deps.iter().enumerate() {
+                writeln!(out, "      dep {}: {}", index, hex::encode(dep)).unwrap();
+            }
+            writeln!(
+                out,
+                "      dep state hash: {}",
+                hex::encode(&header.dep_state_hash)
+            )
+            .unwrap();
+            writeln!(out, "      txs hash: {}", hex::encode(&header.txs_hash)).unwrap();
+            writeln!(out, "      timestamp: {} ({})", header.timestamp, time).unwrap();
+            writeln!(out, "      target: {}", hex::encode(&header.target)).unwrap();
+        }
+        None => writeln!(
+            out,
+            "    header: {} bytes, {}",
+            blob.len(),
+            hex::encode(blob)
+        )
+        .unwrap(),
+    }
+}
+
+/// Readable rendering of a decoded message, submissions are placed on the
+/// chains of a network of `groups` groups.
+pub fn describe(wire: &Wire, groups: u32) -> String {
+    let mut out = String::new();
+    match wire {
+        Wire::Jobs(jobs) => {
+            writeln!(out, "jobs: {}", jobs.len()).unwrap();
+            for job in jobs {
+                writeln!(
+                    out,
+                    "  chain {}-{}: difficulty {:.0}, target {}, txs {} bytes",
+                    job.from,
+                    job.to,
+                    pow::difficulty(&job.target),
+                    hex::encode(&job.target),
+                    job.txs.len()
+                )
+                .unwrap();
+                describe_header(&mut out, &job.header);
+            }
+        }
+        Wire::SubmitReq(req) => {
+            let hash = pow::hash(&req.nonce, &req.header);
+            let (from, to) = pow::chain_index(&hash, groups);
+            writeln!(out, "submit req: chain {}-{}", from, to).unwrap();
+            writeln!(out, "  nonce: {}", hex::encode(&req.nonce)).unwrap();
+            writeln!(out, "  hash: {}", hex::encode(hash)).unwrap();
+            writeln!(out, "  txs: {} bytes", req.txs.len()).unwrap();
+            describe_header(&mut out, &req.header);
+        }
+        Wire::SubmitResult(ret) => {
+            writeln!(
+                out,
+                "submit result: chain {}-{}, status {}",
+                ret.from, ret.to, ret.status
+            )
+            .unwrap();
+        }
+        Wire::NoncePrefix(prefix) => {
+            writeln!(out, "nonce prefix: {:08x}", prefix).unwrap();
+        }
+        Wire::Hello(hello) => {
+            writeln!(out, "hello: rig {}", hello.rig).unwrap();
+            for (group, address) in hello.addresses.iter().enumerate() {
+                writeln!(out, "  group {}: {}", group, address).unwrap();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, describe, encode, parse_header, parse_json, Wire};
+
+    fn header() -> Vec<u8> {
+        let mut header = vec![0u8, 7];
+        for i in 0..9u8 {
+            header.extend(vec![i; 32]);
+        }
+        header.extend(0x17bf884c7a7u64.to_be_bytes());
+        header.extend([0x1e, 0x24, 0x56, 0x71]);
+        header
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = hex::decode("0000000a01000000000000000101").unwrap();
+        let messages = decode(&data).unwrap();
+        assert_eq!(
+            serde_json::to_string(&messages).unwrap(),
+            r#"[{"submit_result":{"from":0,"to":1,"status":true}}]"#
+        );
+        assert_eq!(encode(messages), data);
+        assert!(decode(&data[..8]).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let json = format!(
+            r#"[{{"jobs": [{{"from": 1, "to": 2, "header": "{}", "txs": "aa", "target": "00{}"}}]}},
+                {{"submit_req": {{"nonce": "{}", "header": "01", "txs": "aa"}}}},
+                {{"nonce_prefix": 7}},
+                {{"hello": {{"rig": "rig-1", "addresses": ["1a", "1b"]}}}}]"#,
+            hex::encode(header()),
+            "ff".repeat(31),
+            "00".repeat(24)
+        );
+        let messages = parse_json(&json).unwrap();
+        let data = encode(messages.clone());
+        // kind 0 is told apart by content
+        assert_eq!(decode(&data).unwrap(), messages);
+        let text = describe(&messages[0], 4);
+        assert!(text.contains("chain 1-2: difficulty 256"));
+        assert!(text.contains("timestamp: 1631962056615 (2021-09-18"));
+        assert!(matches!(messages[2], Wire::NoncePrefix(7)));
+        assert!(describe(&messages[3], 4).contains("group 1: 1b"));
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = parse_header(&header()).unwrap();
+        assert_eq!(header.deps.len(), 7);
+        assert_eq!(header.txs_hash, vec![8; 32]);
+        assert_eq!(header.target, vec![0x1e, 0x24, 0x56, 0x71]);
+        assert!(parse_header(&[0, 7, 1]).is_none());
+    }
+}
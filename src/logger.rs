@@ -0,0 +1,158 @@
+use crate::config::{LogConfig, LogFormat};
+use crate::event;
+use log::LevelFilter;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const EVENT_TARGET: &str = "alephium_miner::event";
+
+/// Sets up `env_logger` from `RUST_LOG` with the configured format and
+/// output file. Without `RUST_LOG` the level comes from `conf.level` and
+/// can be changed later, see `set_level`.
+pub fn init(conf: &LogConfig) -> anyhow::Result<()> {
+    let mut builder = env_logger::Builder::from_default_env();
+    //由 log::max_level 过滤，才能在运行中调整
+    if !env_filter() {
+        builder.filter_level(LevelFilter::Trace);
+    }
+    if conf.format == LogFormat::Json {
+        event::set_json(true);
+        builder.format(|buf, record| {
+            //事件本身已是 json
+            if record.target() == EVENT_TARGET {
+                return writeln!(buf, "{}", record.args());
+            }
+            let line = serde_json::json!({
+                "ts": chrono::Utc::now().to_rfc3339(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    if let Some(path) = conf.file.as_ref() {
+        let file = RotatingFile::open(path, conf.max_size * 1024 * 1024, conf.max_files)?;
+        builder.target(env_logger::Target::Pipe(Box::new(file)));
+    }
+    builder.try_init()?;
+    if !env_filter() {
+        set_level(conf.level.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Changes the level of a running logger, `None` logs errors only like
+/// `env_logger` does without `RUST_LOG`.
+pub fn set_level(level: Option<&str>) -> anyhow::Result<()> {
+    if env_filter() {
+        anyhow::bail!("RUST_LOG is set, log.level is ignored");
+    }
+    let level = match level {
+        Some(level) => level
+            .parse::<LevelFilter>()
+            .map_err(|_| anyhow::anyhow!("unknown log level {}", level))?,
+        None => LevelFilter::Error,
+    };
+    log::set_max_level(level);
+    Ok(())
+}
+
+fn env_filter() -> bool {
+    std::env::var_os(env_logger::DEFAULT_FILTER_ENV).is_some()
+}
+
+/// Log file rolled over once it reaches `max_size` bytes: `miner.log` is
+/// renamed to `miner.log.1`, `miner.log.1` to `miner.log.2` and so on, keeping
+/// at most `max_files` old files.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64, //当前文件大小
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, max_size: u64, max_files: usize) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: PathBuf::from(path),
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotatingFile;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("miner-log-{}", rand::random::<u32>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("miner.log");
+        let mut file = RotatingFile::open(path.to_str().unwrap(), 100, 2).unwrap();
+        for i in 0..10 {
+            file.write_all(format!("{:049}\n", i).as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+        // two lines per file, the current one plus two old ones are kept
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.ends_with(&format!("{:049}\n", 9)));
+        let old = fs::read_to_string(dir.join("miner.log.2")).unwrap();
+        assert!(old.starts_with(&format!("{:049}\n", 4)));
+        assert!(!dir.join("miner.log.3").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
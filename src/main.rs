@@ -12,6 +12,7 @@ extern crate serde;
 extern crate hex;
 extern crate num_cpus;
 extern crate serde_derive;
+extern crate serde_json;
 #[macro_use]
 extern crate log;
 extern crate blake3;
@@ -25,34 +26,58 @@ extern crate tokio_util;
 extern crate uuid;
 // extern crate nom;
 
+mod address;
+mod affinity;
 mod amd;
+mod api;
 mod bencher;
+mod benchmark;
 mod config;
 mod connection;
 mod constant;
+mod control;
 mod counter;
+mod ctl;
+mod dashboard;
 mod error;
+mod event;
 mod frame;
 mod gpu;
+mod hook;
+mod http;
+mod inspect;
 mod intel;
+mod logger;
+mod metrics;
 mod miner;
+mod mock_node;
 mod model;
+mod network;
 mod nvidia;
 mod pow;
+mod proxy;
+mod rate;
+mod record;
+mod replay;
+mod sanity;
 mod serder;
 mod task;
+mod throttle;
 mod worker;
 
+use crate::benchmark::Benchmark;
 use crate::frame::Frame;
 use crate::miner::Miner;
+use crate::mock_node::MockNode;
 use crate::model::Message;
-use clap::{App, Arg};
+use crate::network::Network;
+use crate::proxy::Proxy;
+use crate::replay::Replay;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::path::Path;
 
 #[tokio::main]
 async fn main() {
-    let cpu = num_cpus::get();
-    let num_cpu = format!("{}", cpu);
-    let num = num_cpu.as_str();
     let matches = App::new("alephium miner")
         .version("1.0.0")
         .author("知命")
@@ -89,26 +114,708 @@ async fn main() {
                 .short("w")
                 .long("worker")
                 .value_name("worker")
-                .help("worker number")
-                .default_value(num)
+                .help("worker number, default the cpu count")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("config")
+                .help("toml config file, command line options take precedence, default alephium-miner.toml if it exists")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rig")
+                .long("rig")
+                .value_name("rig")
+                .help("rig name reported to pools, proxies and in stats, default the host name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("addresses")
+                .long("addresses")
+                .value_name("addresses")
+                .help("comma separated mining addresses, one per group in group order")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("network")
+                .long("network")
+                .value_name("network")
+                .help("network profile giving the default node port, group count and job checks")
+                .possible_values(&["mainnet", "testnet", "devnet"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("job_policy")
+                .long("job-policy")
+                .value_name("job_policy")
+                .help("what to do with jobs failing the sanity checks: skip them, reconnect or abort")
+                .possible_values(&["skip", "reconnect", "abort"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("groups")
+                .long("groups")
+                .value_name("groups")
+                .help("group count of the network, default inferred from the jobs the node sends")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("share_target")
+                .short("s")
+                .long("share-target")
+                .value_name("share_target")
+                .help("pool mode: hex share target, hashes meeting it are submitted as shares")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tui")
+                .long("tui")
+                .help("show a live dashboard instead of log lines, logs go to alephium-miner.log"),
+        )
+        .arg(
+            Arg::with_name("log_format")
+                .long("log-format")
+                .value_name("log_format")
+                .help("log format: text, json")
+                .possible_values(&["text", "json"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log_file")
+                .long("log-file")
+                .value_name("log_file")
+                .help("write logs to this file, rotated by size")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .value_name("record")
+                .help("record every frame exchanged with the node to this file, see replay")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api")
+                .long("api")
+                .value_name("api")
+                .help("serve /stats, /health and /metrics over http on this address, e.g. 127.0.0.1:8080")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api_control")
+                .long("api-control")
+                .help("also accept runtime control commands on POST /control of the api, without authentication"),
+        )
+        .arg(
+            Arg::with_name("control_socket")
+                .long("control-socket")
+                .value_name("control_socket")
+                .help("accept ctl commands on this unix socket")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pin")
+                .long("pin")
+                .help("pin each worker thread to its own core"),
+        )
+        .arg(
+            Arg::with_name("cores")
+                .long("cores")
+                .value_name("cores")
+                .help("only pin workers to these cores, e.g. 0-7,16-23")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("skip_cores")
+                .long("skip-cores")
+                .value_name("skip_cores")
+                .help("leave these cores free, e.g. 0,1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("numa_spread")
+                .long("numa-spread")
+                .help("pin workers round robin across numa nodes"),
+        )
+        .arg(
+            Arg::with_name("throttle_cpu")
+                .long("throttle-cpu")
+                .value_name("throttle_cpu")
+                .help("percentage of a core each worker may use, sleeping the rest")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_hash_rate")
+                .long("max-hash-rate")
+                .value_name("max_hash_rate")
+                .help("cap of the total hash rate, hashes per second")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("idle_load")
+                .long("idle-load")
+                .value_name("idle_load")
+                .help("only mine while the 1 minute load of other processes stays below this")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("autotune")
+                .long("autotune")
+                .help("benchmark thread count, step size and hash implementation before mining and save the best to the config file"),
+        )
+        .subcommand(
+            SubCommand::with_name("autotune")
+                .about("pick the fastest thread count, step size and hash implementation and save them to the config file")
+                .arg(
+                    Arg::with_name("duration")
+                        .short("d")
+                        .long("duration")
+                        .value_name("duration")
+                        .help("seconds per trial")
+                        .default_value("3")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ctl")
+                .about("control a running miner through its control socket")
+                .arg(
+                    Arg::with_name("command")
+                        .value_name("command")
+                        .help("command to run")
+                        .possible_values(&ctl::COMMANDS)
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("arg")
+                        .value_name("arg")
+                        .help("thread count of set-threads")
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("socket")
+                        .short("s")
+                        .long("socket")
+                        .value_name("socket")
+                        .help("control socket of the miner, default control_socket of the config or /tmp/alephium-miner.sock")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("print the reply as json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("proxy")
+                .about("share one node connection between many miners")
+                .arg(
+                    Arg::with_name("listen")
+                        .short("l")
+                        .long("listen")
+                        .value_name("listen")
+                        .help("address downstream miners connect to")
+                        .default_value("0.0.0.0:10974")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("check the submissions of a --record file and feed it to a local miner")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("file")
+                        .help("recording made with --record")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("benchmark")
+                .about("measure the hash rate of the mining loop per hash implementation and thread count")
+                .arg(
+                    Arg::with_name("duration")
+                        .short("d")
+                        .long("duration")
+                        .value_name("duration")
+                        .help("seconds per run")
+                        .default_value("10")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("threads")
+                        .help("comma separated thread counts, default 1, 2, 4, ... up to the cpu count")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("steps")
+                        .long("steps")
+                        .value_name("steps")
+                        .help("hashes between worker checkpoints, default mining_steps of the config")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("hash")
+                        .long("hash")
+                        .value_name("hash")
+                        .help("comma separated hash implementations: blake3, blake3-merkle")
+                        .default_value("blake3,blake3-merkle")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("print the report as json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("print the protocol messages in hex or a binary file")
+                .arg(
+                    Arg::with_name("hex")
+                        .value_name("hex")
+                        .help("hex encoded messages, read from stdin when neither this nor --file is given")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .short("f")
+                        .long("file")
+                        .value_name("file")
+                        .help("binary file of messages")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("print the json accepted by encode"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("encode")
+                .about("build messages from json and print them as hex")
+                .arg(
+                    Arg::with_name("json")
+                        .value_name("json")
+                        .help("one message or an array of them, read from stdin when not given")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .short("o")
+                        .long("out")
+                        .value_name("out")
+                        .help("write the binary messages to this file instead")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mock-node")
+                .about("fake alephium node with easy targets, for testing")
+                .arg(
+                    Arg::with_name("listen")
+                        .short("l")
+                        .long("listen")
+                        .value_name("listen")
+                        .help("address miners connect to")
+                        .default_value("127.0.0.1:10973")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .value_name("target")
+                        .help("hex target of every job")
+                        .default_value(mock_node::EASY_TARGET)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .value_name("interval")
+                        .help("seconds between job refreshes")
+                        .default_value("10")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("blocks")
+                        .long("blocks")
+                        .value_name("blocks")
+                        .help("exit after accepting this many blocks")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("groups")
+                        .long("groups")
+                        .value_name("groups")
+                        .help("group count of the network, one job per pair of groups")
+                        .default_value("4")
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
-    env_logger::init();
-    info!("starting up");
-    let ip = matches.value_of("ip").unwrap_or("127.0.0.1").to_string();
-    let port = matches.value_of("port").unwrap_or("10973").to_string();
-    let miner_type = matches.value_of("miner_type").unwrap_or("cpu").to_string();
-    let worker_num = matches.value_of("worker").unwrap_or(num);
-    let config = config::Config {
-        ip,
-        port,
-        miner_type,
-        worker_num: worker_num.parse::<usize>().unwrap_or(cpu),
-    };
+    if let Some(matches) = matches.subcommand_matches("decode") {
+        let data = match (matches.value_of("file"), matches.value_of("hex")) {
+            (Some(file), _) => {
+                std::fs::read(file).unwrap_or_else(|err| panic!("read {} error: {}", file, err))
+            }
+            (None, Some(val)) => inspect::parse_hex(val).expect("messages must be hex encoded"),
+            (None, None) => {
+                inspect::parse_hex(&inspect::read_stdin()).expect("messages must be hex encoded")
+            }
+        };
+        match inspect::decode(&data) {
+            Ok(messages) if matches.is_present("json") => {
+                for wire in messages {
+                    println!("{}", serde_json::to_string(&wire).unwrap());
+                }
+            }
+            Ok(messages) => {
+                //按前面收到的任务数推断分组数
+                let mut groups = constant::GROUP_NUMS;
+                for wire in messages.iter() {
+                    if let inspect::Wire::Jobs(jobs) = wire {
+                        groups = network::groups_of(jobs.len()).unwrap_or(groups);
+                    }
+                    print!("{}", inspect::describe(wire, groups));
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("encode") {
+        let input = match matches.value_of("json") {
+            Some(val) => val.to_string(),
+            None => inspect::read_stdin(),
+        };
+        let data = match inspect::parse_json(&input) {
+            Ok(messages) => inspect::encode(messages),
+            Err(err) => {
+                eprintln!("invalid message json: {}", err);
+                std::process::exit(1);
+            }
+        };
+        match matches.value_of("out") {
+            Some(file) => std::fs::write(file, data)
+                .unwrap_or_else(|err| panic!("write {} error: {}", file, err)),
+            None => println!("{}", hex::encode(data)),
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("mock-node") {
+        env_logger::init();
+        let listen = matches.value_of("listen").unwrap_or("127.0.0.1:10973");
+        let target = matches.value_of("target").unwrap_or(mock_node::EASY_TARGET);
+        let interval = matches.value_of("interval").unwrap_or("10");
+        let mut node = MockNode::new(listen.to_string())
+            .with_target(hex::decode(target).expect("target must be hex encoded"))
+            .with_interval(interval.parse::<u64>().expect("interval must be a number"))
+            .with_blocks(
+                matches
+                    .value_of("blocks")
+                    .map(|val| val.parse::<u64>().expect("blocks must be a number")),
+            )
+            .with_groups(
+                matches
+                    .value_of("groups")
+                    .and_then(|val| val.parse::<u32>().ok())
+                    .filter(|val| (1..=network::MAX_GROUPS).contains(val))
+                    .expect("groups must be between 1 and 16"),
+            );
+        node.work().await;
+        return;
+    }
+    let config_path = matches.value_of("config").unwrap_or(config::DEFAULT_PATH);
+    let mut config = load_config(&matches, config_path)
+        .unwrap_or_else(|err| panic!("load config {} error: {}", config_path, err));
+
+    if let Some(matches) = matches.subcommand_matches("ctl") {
+        let name = matches.value_of("command").unwrap();
+        let command = ctl::parse(name, matches.value_of("arg")).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        });
+        let socket = matches
+            .value_of("socket")
+            .map(|val| val.to_string())
+            .or_else(|| config.control_socket.clone())
+            .unwrap_or_else(|| ctl::DEFAULT_SOCKET.to_string());
+        match ctl::request(&socket, &command).await {
+            Ok(Ok(status)) if matches.is_present("json") => {
+                println!("{}", serde_json::to_string_pretty(&status).unwrap())
+            }
+            Ok(Ok(status)) => print!("{}", ctl::describe(name, &status)),
+            Ok(Err(err)) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
 
+    //基准测试不初始化日志，避免任务事件混入报告
+    if let Some(matches) = matches.subcommand_matches("benchmark") {
+        let duration = matches.value_of("duration").unwrap_or("10");
+        let duration = duration.parse::<f64>().expect("duration must be a number");
+        let hash_impls = matches
+            .value_of("hash")
+            .unwrap_or("blake3,blake3-merkle")
+            .split(',')
+            .map(|val| val.trim().parse::<pow::HashImpl>())
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let steps = matches
+            .value_of("steps")
+            .map_or(config.mining_steps, |val| {
+                val.parse::<u64>().expect("steps must be a number")
+            });
+        let mut benchmark = Benchmark::new(&config.miner_type)
+            .with_duration(std::time::Duration::from_secs_f64(duration))
+            .with_mining_steps(steps)
+            .with_hash_impls(hash_impls);
+        if let Some(threads) = matches.value_of("threads") {
+            benchmark = benchmark.with_threads(
+                threads
+                    .split(',')
+                    .map(|val| {
+                        val.trim()
+                            .parse::<usize>()
+                            .expect("threads must be numbers")
+                    })
+                    .collect(),
+            );
+        }
+        let json = matches.is_present("json");
+        //工作线程阻塞等待，不占用异步运行时
+        let report = tokio::task::spawn_blocking(move || {
+            benchmark.run(|run| {
+                if !json {
+                    eprintln!(
+                        "{} x{}: {:.0} hash/s",
+                        run.hash_impl, run.threads, run.hash_rate
+                    );
+                }
+            })
+        })
+        .await
+        .expect("benchmark thread error");
+        match report {
+            Ok(report) if json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+            Ok(report) => print!("{}", benchmark::describe(&report)),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    logger::init(&config.log).expect("init logger error");
+    info!("starting up");
     info!("{:?}", config);
+    let tune = matches.subcommand_matches("autotune");
+    if tune.is_some() || matches.is_present("autotune") {
+        let duration = tune.and_then(|val| val.value_of("duration")).unwrap_or("3");
+        let duration = duration.parse::<f64>().expect("duration must be a number");
+        let ret = autotune(&mut config, config_path, duration).await;
+        if let Err(err) = ret.as_ref() {
+            error!("autotune error {}", err);
+        }
+        if tune.is_some() {
+            std::process::exit(if ret.is_ok() { 0 } else { 1 });
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("proxy") {
+        let listen = matches.value_of("listen").unwrap_or("0.0.0.0:10974");
+        let mut proxy = Proxy::new(config, listen.to_string());
+        proxy.work().await;
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        let file = matches.value_of("file").unwrap();
+        let records = record::load(file)
+            .unwrap_or_else(|err| panic!("load recording {} error: {}", file, err));
+        let mut replay = Replay::new(config, records);
+        replay.work().await;
+        return;
+    }
     let address = format!("{}:{}", config.ip, config.port);
-    let mut miner = Miner::new(config);
-    miner.work().await;
+    let reload: config::Loader = {
+        let matches = matches.clone();
+        let path = config_path.to_string();
+        std::sync::Arc::new(move || load_config(&matches, &path))
+    };
+    let mut miner = Miner::new(config).with_reload(reload);
+    if let Err(err) = miner.work().await {
+        error!("{}", err);
+        //exit 不会执行析构，先把日志写出去
+        log::logger().flush();
+        std::process::exit(1);
+    }
+}
+
+//读取配置文件，再用命令行显式指定的参数覆盖
+fn load_config(matches: &ArgMatches, path: &str) -> anyhow::Result<config::Config> {
+    let mut config = if matches.is_present("config") || Path::new(path).exists() {
+        config::Config::load(path)?
+    } else {
+        config::Config::default()
+    };
+    //命令行显式指定的参数覆盖配置文件
+    if let Some(ip) = explicit(matches, "ip") {
+        config.ip = ip.to_string();
+    }
+    if let Some(port) = explicit(matches, "port") {
+        config.port = port.to_string();
+    }
+    if let Some(miner_type) = explicit(matches, "miner_type") {
+        config.miner_type = miner_type.to_string();
+    }
+    if let Some(worker_num) = explicit(matches, "worker") {
+        config.worker_num = worker_num
+            .parse::<usize>()
+            .unwrap_or_else(|_| num_cpus::get());
+    }
+    if let Some(rig) = matches.value_of("rig") {
+        config.rig = Some(rig.to_string());
+    }
+    if let Some(addresses) = matches.value_of("addresses") {
+        config.addresses = addresses
+            .split(',')
+            .map(|val| val.trim().to_string())
+            .collect();
+    }
+    if let Some(network) = matches.value_of("network") {
+        config.network = Some(network.parse::<Network>()?);
+    }
+    //配置文件和命令行都没有指定端口时，用网络的默认端口
+    if let Some(network) = config.network {
+        if explicit(matches, "port").is_none() && !config::Config::sets(path, "port") {
+            config.port = network.profile().port.to_string();
+        }
+    }
+    match matches.value_of("job_policy") {
+        Some("skip") => config.job_check.policy = config::JobPolicy::Skip,
+        Some("reconnect") => config.job_check.policy = config::JobPolicy::Reconnect,
+        Some("abort") => config.job_check.policy = config::JobPolicy::Abort,
+        _ => {}
+    }
+    if let Some(groups) = matches.value_of("groups") {
+        config.groups = Some(groups.parse::<u32>().expect("groups must be a number"));
+    }
+    if let Some(share_target) = matches.value_of("share_target") {
+        config.share_target = Some(
+            hex::decode(share_target)
+                .map_err(|err| anyhow::anyhow!("share target {}: {}", share_target, err))?,
+        );
+    }
+    if let Some(record) = matches.value_of("record") {
+        config.record = Some(record.to_string());
+    }
+    if let Some(api) = matches.value_of("api") {
+        config.api = Some(api.to_string());
+    }
+    if matches.is_present("api_control") {
+        config.api_control = true;
+    }
+    if let Some(path) = matches.value_of("control_socket") {
+        config.control_socket = Some(path.to_string());
+    }
+    if matches.is_present("tui") {
+        config.tui = true;
+    }
+    if matches.is_present("pin") {
+        config.affinity.pin = true;
+    }
+    if let Some(cores) = matches.value_of("cores") {
+        config.affinity.cores = Some(
+            affinity::parse_list(cores)
+                .ok_or_else(|| anyhow::anyhow!("cores must be a list like 0-7,16: {}", cores))?,
+        );
+    }
+    if let Some(cores) = matches.value_of("skip_cores") {
+        config.affinity.skip = affinity::parse_list(cores)
+            .ok_or_else(|| anyhow::anyhow!("skip cores must be a list like 0,1: {}", cores))?;
+    }
+    if matches.is_present("numa_spread") {
+        config.affinity.numa_spread = true;
+    }
+    if let Some(cpu) = matches.value_of("throttle_cpu") {
+        config.throttle.cpu = Some(cpu.parse::<f64>().expect("throttle cpu must be a number"));
+    }
+    if let Some(rate) = matches.value_of("max_hash_rate") {
+        config.throttle.hash_rate =
+            Some(rate.parse::<f64>().expect("max hash rate must be a number"));
+    }
+    if let Some(load) = matches.value_of("idle_load") {
+        config.throttle.idle_load = Some(load.parse::<f64>().expect("idle load must be a number"));
+    }
+    match matches.value_of("log_format") {
+        Some("json") => config.log.format = config::LogFormat::Json,
+        Some("text") => config.log.format = config::LogFormat::Text,
+        _ => {}
+    }
+    if let Some(file) = matches.value_of("log_file") {
+        config.log.file = Some(file.to_string());
+    }
+    //仪表盘占用终端，日志改写到文件
+    if config.tui && config.log.file.is_none() {
+        config.log.file = Some("alephium-miner.log".to_string());
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+//调优结果写回配置文件，本次运行直接使用
+async fn autotune(config: &mut config::Config, path: &str, duration: f64) -> anyhow::Result<()> {
+    let benchmark = Benchmark::new(&config.miner_type)
+        .with_duration(std::time::Duration::from_secs_f64(duration))
+        .with_mining_steps(config.mining_steps);
+    let tuning = tokio::task::spawn_blocking(move || {
+        benchmark.autotune(|run| {
+            info!(
+                "autotune {} threads {} steps {}: {:.0} hash/s",
+                run.hash_impl, run.threads, run.mining_steps, run.hash_rate
+            )
+        })
+    })
+    .await??;
+    info!(
+        "autotune picked {} threads {} steps {}: {:.0} hash/s, saved to {}",
+        tuning.hash_impl, tuning.threads, tuning.mining_steps, tuning.hash_rate, path
+    );
+    config.worker_num = tuning.threads;
+    config.mining_steps = tuning.mining_steps;
+    config.hash_impl = tuning.hash_impl;
+    let mut values = toml::value::Table::new();
+    values.insert("worker_num".to_string(), (tuning.threads as i64).into());
+    values.insert(
+        "mining_steps".to_string(),
+        (tuning.mining_steps as i64).into(),
+    );
+    values.insert("hash_impl".to_string(), tuning.hash_impl.to_string().into());
+    config::Config::persist(path, values)
+}
+
+//只返回命令行上显式给出的参数，默认值不覆盖配置文件
+fn explicit<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    if matches.occurrences_of(name) > 0 {
+        matches.value_of(name)
+    } else {
+        None
+    }
 }
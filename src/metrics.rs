@@ -0,0 +1,325 @@
+//! Prometheus text exposition of the `Counter` stats, served on `/metrics`.
+//! Every metric is prefixed with `alephium_miner_`, chain labels are `from`
+//! and `to`, worker labels are `worker` and `backend`.
+
+use crate::counter::{Stats, Summary};
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.out, "# HELP alephium_miner_{} {}", name, help).unwrap();
+        writeln!(self.out, "# TYPE alephium_miner_{} {}", name, kind).unwrap();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, String)], value: f64) {
+        write!(self.out, "alephium_miner_{}", name).unwrap();
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, val)| format!("{}=\"{}\"", key, escape(val)))
+                .collect();
+            write!(self.out, "{{{}}}", labels.join(",")).unwrap();
+        }
+        writeln!(self.out, " {}", value).unwrap();
+    }
+
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: f64) {
+        self.family(name, kind, help);
+        self.sample(name, &[], value);
+    }
+
+    fn summary(&mut self, name: &str, help: &str, val: &Summary) {
+        self.family(name, "summary", help);
+        self.sample(&format!("{}_sum", name), &[], val.sum);
+        self.sample(&format!("{}_count", name), &[], val.count as f64);
+    }
+}
+
+fn escape(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn render(stats: &Stats) -> String {
+    let mut exp = Exposition { out: String::new() };
+    exp.single(
+        "uptime_seconds",
+        "gauge",
+        "Seconds since the miner started.",
+        stats.uptime as f64,
+    );
+    exp.family(
+        "info",
+        "gauge",
+        "Always 1, labeled with the rig name and backend.",
+    );
+    exp.sample(
+        "info",
+        &[
+            ("rig", stats.rig.clone()),
+            ("backend", stats.backend.clone()),
+        ],
+        1f64,
+    );
+    exp.single(
+        "groups",
+        "gauge",
+        "Group count of the network, configured or inferred from the jobs.",
+        stats.groups as f64,
+    );
+    exp.single(
+        "connected",
+        "gauge",
+        "1 if the node connection is up, 0 otherwise.",
+        if stats.connection.connected {
+            1f64
+        } else {
+            0f64
+        },
+    );
+    exp.single(
+        "reconnects_total",
+        "counter",
+        "Connections to the node after the first one.",
+        stats.reconnects as f64,
+    );
+
+    exp.family(
+        "hashes_total",
+        "counter",
+        "Hashes computed, per worker thread and backend.",
+    );
+    for worker in stats.workers.iter() {
+        exp.sample(
+            "hashes_total",
+            &[
+                ("worker", worker.id.clone()),
+                ("backend", stats.backend.clone()),
+            ],
+            worker.hash_count as f64,
+        );
+    }
+    exp.family(
+        "hash_rate",
+        "gauge",
+        "Average hashes per second since start, per backend.",
+    );
+    exp.sample(
+        "hash_rate",
+        &[("backend", stats.backend.clone())],
+        stats.hash_rate as f64,
+    );
+    exp.family(
+        "hash_rate_window",
+        "gauge",
+        "Hashes per second as moving averages over 10s, 1m and 15m windows, per backend.",
+    );
+    for (window, rate) in stats.hash_rates.windows() {
+        exp.sample(
+            "hash_rate_window",
+            &[
+                ("backend", stats.backend.clone()),
+                ("window", window.to_string()),
+            ],
+            rate,
+        );
+    }
+    exp.family(
+        "worker_hash_rate_window",
+        "gauge",
+        "Hashes per second as moving averages over 10s, 1m and 15m windows, per worker thread.",
+    );
+    for worker in stats.workers.iter() {
+        for (window, rate) in worker.hash_rates.windows() {
+            exp.sample(
+                "worker_hash_rate_window",
+                &[
+                    ("worker", worker.id.clone()),
+                    ("backend", stats.backend.clone()),
+                    ("window", window.to_string()),
+                ],
+                rate,
+            );
+        }
+    }
+    exp.single(
+        "effective_hash_rate",
+        "gauge",
+        "Hashes per second implied by the submitted shares.",
+        stats.effective_hash_rate as f64,
+    );
+    exp.single(
+        "submitted_total",
+        "counter",
+        "Blocks and shares sent to the node.",
+        stats.submitted as f64,
+    );
+
+    let chain_families = [
+        ("blocks_found_total", "counter", "Blocks found, per chain."),
+        ("shares_found_total", "counter", "Shares found, per chain."),
+        (
+            "submissions_accepted_total",
+            "counter",
+            "Blocks accepted by the node, per chain.",
+        ),
+        (
+            "submissions_rejected_total",
+            "counter",
+            "Blocks rejected by the node, per chain.",
+        ),
+        (
+            "shares_accepted_total",
+            "counter",
+            "Shares accepted by the pool, per chain.",
+        ),
+        (
+            "shares_rejected_total",
+            "counter",
+            "Shares rejected by the pool, per chain.",
+        ),
+        (
+            "job_difficulty",
+            "gauge",
+            "Difficulty of the current job, per chain.",
+        ),
+        (
+            "job_age_seconds",
+            "gauge",
+            "Seconds since the current job arrived, per chain.",
+        ),
+    ];
+    for (name, kind, help) in chain_families.iter() {
+        exp.family(name, kind, help);
+        for chain in stats.chains.iter() {
+            let value = match *name {
+                "blocks_found_total" => chain.found as f64,
+                "shares_found_total" => chain.shares as f64,
+                "submissions_accepted_total" => chain.accepted as f64,
+                "submissions_rejected_total" => chain.rejected as f64,
+                "shares_accepted_total" => chain.shares_accepted as f64,
+                "shares_rejected_total" => chain.shares_rejected as f64,
+                "job_difficulty" => chain.difficulty,
+                _ => chain.job_age,
+            };
+            exp.sample(
+                name,
+                &[
+                    ("from", chain.from.to_string()),
+                    ("to", chain.to.to_string()),
+                ],
+                value,
+            );
+        }
+    }
+
+    exp.family(
+        "stale_tasks_total",
+        "counter",
+        "Work made stale by new jobs: dropped from the queue, interrupted while mining, or abandoned at the hash limit.",
+    );
+    for (reason, count) in [
+        ("dropped", stats.stale.dropped),
+        ("interrupted", stats.stale.interrupted),
+        ("abandoned", stats.stale.abandoned),
+    ] {
+        exp.sample(
+            "stale_tasks_total",
+            &[("reason", reason.to_string())],
+            count as f64,
+        );
+    }
+    exp.family(
+        "rejected_jobs_total",
+        "counter",
+        "Jobs from the node that failed the sanity checks, per reason.",
+    );
+    for (reason, count) in stats.rejected_jobs.iter() {
+        exp.sample(
+            "rejected_jobs_total",
+            &[("reason", reason.clone())],
+            *count as f64,
+        );
+    }
+    exp.summary(
+        "job_latency_seconds",
+        "Time from job arrival to a worker starting it.",
+        &stats.job_latency,
+    );
+    exp.summary(
+        "submit_round_trip_seconds",
+        "Time from submission to the node's result.",
+        &stats.submit_round_trip,
+    );
+    exp.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::counter::Counter;
+    use crate::model::Job;
+    use crate::task::Task;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render() {
+        let mut counter = Counter::new()
+            .with_backend("cpu")
+            .with_identity("rig-1", vec![]);
+        counter.connected("127.0.0.1:10973");
+        counter.disconnected();
+        counter.connected("127.0.0.1:10973");
+        let job = Job {
+            from: 1,
+            to: 2,
+            target: vec![0xff; 31],
+            ..Default::default()
+        };
+        counter.job_received(&job);
+        counter.add(
+            Task::new()
+                .with_job(job)
+                .with_worker_id("w\"1".to_string())
+                .with_hash_count(100)
+                .with_status(0)
+                .start(),
+        );
+        counter.record_hashes("w\"1", 100);
+        counter.submit_result(1, 2, true, false);
+        counter.submit_result(1, 2, true, true);
+        counter.submit_result(1, 2, false, true);
+        counter.submit_round_trip(Duration::from_millis(500));
+        counter.dropped(3);
+        counter.rejected_job("zero_target");
+
+        let text = render(&counter.stats());
+        for line in [
+            "# TYPE alephium_miner_hashes_total counter",
+            "alephium_miner_connected 1",
+            "alephium_miner_info{rig=\"rig-1\",backend=\"cpu\"} 1",
+            "alephium_miner_reconnects_total 1",
+            "alephium_miner_groups 4",
+            "alephium_miner_hashes_total{worker=\"w\\\"1\",backend=\"cpu\"} 100",
+            "alephium_miner_blocks_found_total{from=\"1\",to=\"2\"} 1",
+            "alephium_miner_submissions_accepted_total{from=\"1\",to=\"2\"} 1",
+            "alephium_miner_submissions_rejected_total{from=\"1\",to=\"2\"} 0",
+            "alephium_miner_shares_accepted_total{from=\"1\",to=\"2\"} 1",
+            "alephium_miner_shares_rejected_total{from=\"1\",to=\"2\"} 1",
+            "alephium_miner_stale_tasks_total{reason=\"dropped\"} 3",
+            "alephium_miner_rejected_jobs_total{reason=\"zero_target\"} 1",
+            "alephium_miner_job_latency_seconds_count 1",
+            "# TYPE alephium_miner_hash_rate_window gauge",
+            "alephium_miner_submit_round_trip_seconds_sum 0.5",
+        ] {
+            assert!(text.lines().any(|val| val == line), "missing {}", line);
+        }
+    }
+}
@@ -1,18 +1,36 @@
-use crate::counter::Counter;
-use crate::model::Body;
-use crate::model::WorkUnit;
+use crate::affinity::{self, Topology};
+use crate::config::JobPolicy;
+use crate::control::{self, Command, Control, JobStatus, Reply, Status, WorkerStatus};
+use crate::counter::{Counter, SharedCounter};
+use crate::dashboard::Dashboard;
+use crate::event::Event;
+use crate::hook::{BlockEvent, Hook};
+use crate::model::{Body, Hello};
+use crate::model::{Job, WorkUnit};
+use crate::network::Groups;
+use crate::record::{Direction, Recorder};
+use crate::sanity::Checker;
 use crate::task::Task;
-use crate::worker::{Notifier, Worker};
-use crate::{config, connection, Frame, Message};
+use crate::throttle::{self, SharedThrottle, Throttle};
+use crate::worker::{Notifier, SharedNotifiers, Worker};
+use crate::{api, config, connection, constant, ctl, logger, pow, Frame, Message};
 use crossbeam;
+use parking_lot::RwLock;
 use std::clone::Clone;
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use threadpool;
-use tokio::sync::mpsc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, oneshot};
 
 pub struct Miner {
     pool: threadpool::ThreadPool,
     conf: config::Config,
+    counter: SharedCounter,
+    control: (Control, Option<mpsc::Receiver<control::Request>>),
+    reload: Option<config::Loader>,
 }
 
 impl Miner {
@@ -27,94 +45,452 @@ impl Miner {
                 .num_threads(conf.worker_num)
                 .thread_name(format!("{}", "miner"))
                 .build(),
+            counter: Counter::new()
+                .with_backend(&conf.miner_type)
+                .with_identity(&conf.rig_name(), conf.addresses.clone())
+                .with_history_size(conf.history_size)
+                .with_interval(conf.stats_interval)
+                .shared(),
+            control: {
+                let (control, rx) = Control::channel();
+                (control, Some(rx))
+            },
+            reload: None,
             conf,
         }
     }
 
-    pub async fn work(&mut self) {
+    /// Lets the `reload_config` command read the config again.
+    pub fn with_reload(mut self, reload: config::Loader) -> Self {
+        self.reload = Some(reload);
+        self
+    }
+
+    /// Commands for the running miner, see `control::Command`.
+    pub fn control(&self) -> Control {
+        self.control.0.clone()
+    }
+
+    /// Mines until the job checks abort it, see `JobPolicy::Abort`.
+    pub async fn work(&mut self) -> Result<(), String> {
         let option = bincode::config::Configuration::standard()
             .with_big_endian()
             .with_no_limit()
             .with_fixed_int_encoding();
-        let address = format!("{}:{}", self.conf.ip, self.conf.port);
-        let client = tokio::net::TcpStream::connect(address).await.unwrap();
+        //重新加载配置时可能更换
+        let endpoint = Arc::new(RwLock::new(self.conf.nodes()));
         let (tcp_tx, mut tcp_rx) = mpsc::channel::<Task>(100 * self.conf.worker_num);
         let (scheduler_tx, scheduler_rx) = mpsc::channel::<Unit>(100 * self.conf.worker_num);
+        let (writer_tx, mut writer_rx) = mpsc::channel::<connection::Writer>(1);
+        let (reconnect_tx, mut reconnect_rx) = mpsc::channel::<()>(1);
+        //除提交外发往节点的消息
+        let (out_tx, mut out_rx) = mpsc::channel::<Message>(8);
+        let rig = self.conf.rig_name();
+        info!("rig {}", rig);
+        if let Some(network) = self.conf.network {
+            let profile = network.profile();
+            info!(
+                "network {}: {} groups, {} jobs expected",
+                network,
+                Groups::from_config(&self.conf).get(),
+                self.conf
+                    .groups
+                    .map_or(profile.jobs(), |val| (val * val) as usize)
+            );
+        }
+        for (group, address) in self.conf.addresses.iter().enumerate() {
+            info!("group {} address {}", group, address);
+        }
+        let hello = Message::hello(Hello {
+            rig,
+            addresses: self.conf.addresses.clone(),
+        });
+        let pool = self.conf.share_target.is_some();
         let scheduler_tx_clone = scheduler_tx.clone();
-        let (mut r, mut w) = connection::pair(client);
 
+        if let Some(listen) = self.conf.api.as_ref() {
+            let listener = TcpListener::bind(listen)
+                .await
+                .unwrap_or_else(|err| panic!("api listen {} error: {}", listen, err));
+            info!("stats api listening on {}", listen);
+            //控制命令没有认证，只在显式开启时提供
+            let control = self.conf.api_control.then(|| self.control());
+            if control.is_some()
+                && !listener
+                    .local_addr()
+                    .is_ok_and(|val| val.ip().is_loopback())
+            {
+                warn!(
+                    "api control on {} is reachable from other hosts without authentication",
+                    listen
+                );
+            }
+            api::serve(listener, self.counter.clone(), control);
+        }
+        if let Some(path) = self.conf.control_socket.as_ref() {
+            ctl::serve(path, self.control())
+                .await
+                .unwrap_or_else(|err| panic!("control socket {} error: {}", path, err));
+            info!("control socket listening on {}", path);
+        }
+        if self.reload.is_some() {
+            tokio::spawn(reload_on_hangup(self.control()));
+        }
+
+        let recorder = self.conf.record.as_ref().map(|path| {
+            Arc::new(
+                Recorder::create(path)
+                    .unwrap_or_else(|err| panic!("create record file {} error: {}", path, err)),
+            )
+        });
+
+        //读取节点消息，连接断开后重连
+        let counter = self.counter.clone();
+        let left_recorder = recorder.clone();
+        let left_endpoint = endpoint.clone();
         let left_half = tokio::spawn(async move {
+            //断线后换下一个节点，试完一轮再等待
+            let mut nodes: Vec<String> = vec![];
+            let mut index = 0;
             loop {
-                match r.read_frame().await {
-                    Ok(val) => match val {
-                        Some(val) => {
-                            if let Frame::Bulk(bytes) = val {
-                                let (msg, size) = bincode::decode_from_slice::<Message, _>(
-                                    bytes.as_ref(),
-                                    option,
-                                )
-                                .expect("decode_from_slice msg error");
+                let current = left_endpoint.read().clone();
+                if current != nodes {
+                    nodes = current;
+                    index = 0;
+                }
+                let address = nodes[index].clone();
+                index = (index + 1) % nodes.len();
+                let client = match TcpStream::connect(&address).await {
+                    Ok(client) => client,
+                    Err(err) => {
+                        Event::Reconnect {
+                            endpoint: address.clone(),
+                            connected: false,
+                            error: Some(err.to_string()),
+                        }
+                        .emit();
+                        if index == 0 {
+                            tokio::time::sleep(Duration::from_secs(constant::RECONNECT_DELAY))
+                                .await;
+                        }
+                        continue;
+                    }
+                };
+                Event::Reconnect {
+                    endpoint: address.clone(),
+                    connected: true,
+                    error: None,
+                }
+                .emit();
+                counter.lock().connected(&address);
+                //断线期间的重连请求已经没有意义
+                while reconnect_rx.try_recv().is_ok() {}
+                let (mut r, w) = connection::pair(client);
+                writer_tx.send(w).await;
+                //矿池连接后即发送身份，代理在分配 nonce 前缀后发送，节点不支持
+                let mut greeted = pool;
+                if pool {
+                    out_tx.send(hello.clone()).await;
+                }
+                let mut requested = false;
+                let reason = loop {
+                    let frame = tokio::select! {
+                        frame = r.read_frame() => frame,
+                        Some(_) = reconnect_rx.recv() => {
+                            requested = true;
+                            break "reconnect requested".to_string();
+                        }
+                    };
+                    match frame {
+                        Ok(Some(Frame::Bulk(bytes))) => {
+                            if let Some(recorder) = left_recorder.as_ref() {
+                                recorder.record(Direction::In, &bytes);
+                            }
+                            match bincode::decode_from_slice::<Message, _>(bytes.as_ref(), option) {
                                 //send Scheduler
-                                scheduler_tx_clone.send(Unit::MSG(msg)).await;
+                                Ok((msg, _)) => {
+                                    if msg.is_nonce_prefix() && !greeted {
+                                        out_tx.send(hello.clone()).await;
+                                        greeted = true;
+                                    }
+                                    scheduler_tx_clone.send(Unit::MSG(msg)).await;
+                                }
+                                Err(err) => error!("decode_from_slice msg error {:?}", err),
                             }
                         }
-                        None => unreachable!(),
-                    },
-                    Err(err) => error!("read_frame error {}", err),
+                        Ok(Some(_)) => {}
+                        Ok(None) => break "connection closed by node".to_string(),
+                        Err(err) => break format!("read_frame error {}", err),
+                    }
+                };
+                counter.lock().disconnected();
+                Event::Reconnect {
+                    endpoint: address.clone(),
+                    connected: false,
+                    error: Some(reason),
+                }
+                .emit();
+                if !requested && index == 0 {
+                    tokio::time::sleep(Duration::from_secs(constant::RECONNECT_DELAY)).await;
                 }
             }
         });
+        let counter = self.counter.clone();
         let right_half = tokio::spawn(async move {
+            let mut writer: Option<connection::Writer> = None;
             loop {
-                if let Some(val) = tcp_rx.recv().await {
-                    //send Scheduler
-                    scheduler_tx.send(Unit::TASK(val.clone())).await;
-                    if val.status() == 0 {
-                        let msg = Message::submit_req(val.into());
+                tokio::select! {
+                    //先换上新连接，再发送属于它的消息
+                    biased;
+                    Some(w) = writer_rx.recv() => {
+                        //旧连接上的提交不会再有结果
+                        scheduler_tx.send(Unit::Connected).await;
+                        writer = Some(w);
+                    }
+                    Some(msg) = out_rx.recv() => {
+                        let w = match writer.as_mut() {
+                            Some(w) => w,
+                            None => continue,
+                        };
                         let data =
                             bincode::encode_to_vec(msg, option).expect("encode_to_vec msg error");
-                        //send server
+                        if let Some(recorder) = recorder.as_ref() {
+                            recorder.record(Direction::Out, &data);
+                        }
                         if let Err(err) = w.write_frame(&Frame::Bulk(data)).await {
                             error!("write_frame error {}", err);
+                            writer = None;
+                        }
+                    }
+                    Some(val) = tcp_rx.recv() => {
+                        //send Scheduler
+                        scheduler_tx.send(Unit::TASK(val.clone())).await;
+                        //0: 区块, 4: 矿池份额
+                        if val.status() != 0 && val.status() != 4 {
+                            continue;
+                        }
+                        let w = match writer.as_mut() {
+                            Some(w) => w,
+                            None => {
+                                warn!("not connected to node, drop submission");
+                                continue;
+                            }
+                        };
+                        let sent = Event::SubmitSent {
+                            from: val.job().from,
+                            to: val.job().to,
+                            nonce: hex::encode(val.nonce()),
+                            share: val.status() == 4,
+                        };
+                        let submitted = Submitted::new(&val);
+                        let msg = Message::submit_req(val.into());
+                        let data =
+                            bincode::encode_to_vec(msg, option).expect("encode_to_vec msg error");
+                        if let Some(recorder) = recorder.as_ref() {
+                            recorder.record(Direction::Out, &data);
+                        }
+                        //send server
+                        match w.write_frame(&Frame::Bulk(data)).await {
+                            Ok(_) => {
+                                counter.lock().submitted();
+                                sent.emit();
+                                scheduler_tx.send(Unit::Submitted(submitted)).await;
+                            }
+                            Err(err) => {
+                                error!("write_frame error {}", err);
+                                writer = None;
+                            }
                         }
                     }
+                    else => break,
                 }
             }
         });
 
-        let mut notifiters = vec![];
         let (tx, rx) = crossbeam::channel::bounded::<WorkUnit>(100000);
-        let mut thread_count = 0;
-        while thread_count < self.conf.worker_num {
-            thread_count += 1;
-            let mut worker = Worker::new(tcp_tx.clone(), rx.clone());
-            let notifier = worker.notifier();
-            self.pool.execute(move || worker.work());
-            notifiters.push(Arc::new(notifier));
+        let mut spawner = Spawner::new(
+            self.pool.clone(),
+            self.conf.clone(),
+            tcp_tx.clone(),
+            rx.clone(),
+            self.counter.clone(),
+        );
+        if spawner.throttle.read().is_enabled() {
+            info!("throttle workers {:?}", self.conf.throttle);
+        }
+        let notifiters: SharedNotifiers = Default::default();
+        for index in 0..self.conf.worker_num {
+            let notifier = spawner.spawn(index);
+            notifiters.write().push(notifier);
+        }
+        if let Some(threshold) = self.conf.throttle.idle_load {
+            tokio::spawn(throttle::idle_watch(threshold, notifiters.clone()));
+        }
+        if self.conf.tui {
+            let mut dashboard = Dashboard::new(self.counter.clone(), notifiters.clone());
+            std::thread::spawn(move || {
+                if let Err(err) = dashboard.run() {
+                    error!("dashboard error {}", err);
+                }
+                //退出仪表盘即退出矿工
+                std::process::exit(0);
+            });
         }
         let mut scheduler = Scheduler::new()
+            .with_share_target(self.conf.share_target.clone())
+            .with_nonce_prefix(rand::random())
+            .with_groups(Groups::from_config(&self.conf))
+            .with_hook(Hook::new(self.conf.notify.clone()))
+            .with_counter(self.counter.clone())
             .with_rx(scheduler_rx)
             .with_notifier(notifiters)
+            .with_spawner(spawner)
+            .with_control(self.control.1.take().expect("miner started twice"))
+            .with_reconnect(reconnect_tx)
+            .with_endpoint(endpoint)
+            .with_config(self.conf.clone())
+            .with_reload(self.reload.clone())
+            .with_receiver(rx)
             .with_sender(tx);
 
         let scheduler = tokio::spawn(async move { scheduler.work().await });
-        scheduler.await;
-        left_half.await;
-        right_half.await;
+        let ret = scheduler.await.expect("scheduler panicked");
+        //调度器只在放弃挖矿时返回，断开节点连接
+        left_half.abort();
+        right_half.abort();
+        ret
     }
 }
 
-enum Unit {
+pub(crate) enum Unit {
     MSG(Message),
     TASK(Task),
+    Submitted(Submitted),       //已写入节点连接的提交
+    Connected,                  //连接了新的节点连接
+    Flush(oneshot::Sender<()>), //前面的消息都已处理完时回复，回放用来同步
+}
+
+//等待节点结果的区块或份额，不保留任务数据
+pub(crate) struct Submitted {
+    chain: (u32, u32),
+    task_id: u64,
+    block: Option<BlockEvent>, //区块的通知内容，份额为 None
+    time: Instant,
+}
+
+impl Submitted {
+    fn new(task: &Task) -> Submitted {
+        Submitted {
+            chain: (task.job().from, task.job().to),
+            task_id: task.task_id(),
+            block: (task.status() == 0).then(|| BlockEvent::new(task, false)),
+            time: Instant::now(),
+        }
+    }
+}
+
+/// Starts worker threads, at startup and when the `Scheduler` adds them at
+/// runtime.
+pub struct Spawner {
+    pool: threadpool::ThreadPool,
+    conf: config::Config,
+    sender: mpsc::Sender<Task>,
+    receiver: crossbeam::channel::Receiver<WorkUnit>,
+    counter: SharedCounter,
+    topology: Topology,
+    throttle: SharedThrottle,
+    rig: String,
+    spawned: usize, //已启动的工作线程数，用于编号
+}
+
+impl Spawner {
+    pub fn new(
+        pool: threadpool::ThreadPool,
+        conf: config::Config,
+        sender: mpsc::Sender<Task>,
+        receiver: crossbeam::channel::Receiver<WorkUnit>,
+        counter: SharedCounter,
+    ) -> Spawner {
+        let throttle = Throttle::new(&conf.throttle, conf.worker_num);
+        Spawner {
+            pool,
+            sender,
+            receiver,
+            counter,
+            topology: Topology::detect(),
+            throttle: Arc::new(RwLock::new(throttle)),
+            rig: conf.rig_name(),
+            spawned: 0,
+            conf,
+        }
+    }
+
+    /// Starts the `index`th worker, pinned to its core if configured. Ids
+    /// are `rig-n`, numbered in start order so they repeat across runs.
+    pub fn spawn(&mut self, index: usize) -> Arc<Notifier> {
+        let mut worker = Worker::new(self.sender.clone(), self.receiver.clone())
+            .with_worker_id(format!("{}-{}", self.rig, self.spawned))
+            .with_mining_steps(self.conf.mining_steps)
+            .with_hash_impl(self.conf.hash_impl)
+            .with_throttle(self.throttle.clone());
+        self.spawned += 1;
+        let notifier = worker.notifier();
+        let placement = self
+            .topology
+            .assign(&self.conf.affinity, index + 1)
+            .map(|val| val[index]);
+        if let Some(placement) = placement {
+            info!(
+                "worker {} {} pinned to core {} (numa node {})",
+                index,
+                notifier.worker_id(),
+                placement.core,
+                placement.node
+            );
+            self.counter.lock().placed(notifier.worker_id(), placement);
+        }
+        //被移除的工作线程可能还没退出，线程池不够时扩容
+        let needed = self.pool.active_count() + self.pool.queued_count() + 1;
+        if needed > self.pool.max_count() {
+            self.pool.set_num_threads(needed);
+        }
+        self.pool.execute(move || {
+            if let Some(placement) = placement {
+                affinity::pin(placement.core);
+            }
+            worker.work()
+        });
+        Arc::new(notifier)
+    }
+
+    //算力上限按工作线程数平分
+    fn rebalance(&self, workers: usize) {
+        *self.throttle.write() = Throttle::new(&self.conf.throttle, workers);
+    }
 }
 
 #[derive(Default)]
 pub struct Scheduler {
     rx: Option<mpsc::Receiver<Unit>>,
     sender: Option<crossbeam::channel::Sender<WorkUnit>>,
-    notifier: Vec<Arc<Notifier>>,
-    pending_tasks: Vec<(Task, bool)>,
+    receiver: Option<crossbeam::channel::Receiver<WorkUnit>>, //用于丢弃过期任务
+    notifier: SharedNotifiers,
+    spawner: Option<Spawner>,
+    control: Option<mpsc::Receiver<control::Request>>,
+    reconnect: Option<mpsc::Sender<()>>, //通知读取节点消息的任务断开重连
+    reload: Option<config::Loader>,
+    conf: config::Config,               //最近一次加载的配置，重新加载时与之比较
+    endpoint: Arc<RwLock<Vec<String>>>, //依次尝试的节点地址
+    paused: bool,                       //全局暂停，新增的工作线程也保持暂停
+    pending_tasks: VecDeque<Submitted>, //已提交、等待节点结果的区块和份额，最多 MAX_PENDING_SUBMITS 个
+    jobs: Vec<JobStatus>,               //最近一批任务
+    jobs_time: Option<Instant>,
+    hook: Hook,
+    counter: SharedCounter,
+    share_target: Option<Vec<u8>>,
+    nonce_prefix: u32,
+    groups: Groups,                 //网络分组数，配置指定或由任务数推断
+    rejected_time: Option<Instant>, //上次因任务未通过检查而重连的时间
 }
 
 impl Scheduler {
@@ -132,24 +508,150 @@ impl Scheduler {
         self
     }
 
-    pub fn with_notifier(mut self, w: Vec<Arc<Notifier>>) -> Self {
+    pub fn with_receiver(mut self, receiver: crossbeam::channel::Receiver<WorkUnit>) -> Self {
+        self.receiver = Some(receiver);
+        self
+    }
+
+    pub fn with_share_target(mut self, target: Option<Vec<u8>>) -> Self {
+        self.share_target = target;
+        self
+    }
+
+    pub fn with_nonce_prefix(mut self, prefix: u32) -> Self {
+        self.nonce_prefix = prefix;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: Groups) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub fn with_hook(mut self, hook: Hook) -> Self {
+        self.hook = hook;
+        self
+    }
+
+    pub fn with_counter(mut self, counter: SharedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn with_notifier(mut self, w: SharedNotifiers) -> Self {
         self.notifier = w;
         self
     }
 
-    pub async fn work(&mut self) {
-        let mut counter = Counter::new();
-        let count = 0;
+    pub fn with_spawner(mut self, spawner: Spawner) -> Self {
+        self.spawner = Some(spawner);
+        self
+    }
+
+    pub fn with_control(mut self, control: mpsc::Receiver<control::Request>) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: mpsc::Sender<()>) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
+    pub fn with_reload(mut self, reload: Option<config::Loader>) -> Self {
+        self.reload = reload;
+        self
+    }
+
+    pub fn with_config(mut self, conf: config::Config) -> Self {
+        self.conf = conf;
+        self
+    }
+
+    pub fn with_endpoint(mut self, endpoint: Arc<RwLock<Vec<String>>>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Schedules jobs until a batch fails the checks under the abort
+    /// policy, returning why.
+    pub async fn work(&mut self) -> Result<(), String> {
+        let mut progress = tokio::time::interval(Duration::from_secs(constant::PROGRESS_INTERVAL));
 
         loop {
-            if let Some(val) = self.rx.as_mut().unwrap().recv().await {
+            let val = tokio::select! {
+                _ = progress.tick() => {
+                    self.collect_progress();
+                    continue;
+                }
+                Some((command, reply)) = recv_control(&mut self.control) => {
+                    let _ = reply.send(self.command(command));
+                    continue;
+                }
+                val = self.rx.as_mut().unwrap().recv() => val,
+            };
+            if let Some(val) = val {
                 match val {
                     Unit::MSG(msg) => {
                         match msg.into() {
                             Body::Jobs(jobs) => {
+                                //先检查，有问题的批次不改变推断的分组数
+                                let count = jobs.len();
+                                let jobs = match self.sanitize(jobs)? {
+                                    Some(jobs) => jobs,
+                                    None => continue,
+                                };
+                                if let Some(groups) = self.groups.update(count) {
+                                    self.counter.lock().set_groups(groups);
+                                    let addresses = self.conf.addresses.len();
+                                    if addresses > 0 && addresses != groups as usize {
+                                        error!(
+                                            "{} addresses configured, the network has {} groups",
+                                            addresses, groups
+                                        );
+                                    }
+                                }
+                                //只挖固定的链，修改后下一批任务生效
+                                let jobs: Vec<Job> = jobs
+                                    .into_iter()
+                                    .filter(|job| self.conf.mines(job.from, job.to))
+                                    .collect();
+                                //新任务到达，旧任务作废
+                                if let Some(receiver) = self.receiver.as_ref() {
+                                    let dropped = receiver.try_iter().count();
+                                    self.counter.lock().dropped(dropped as u64);
+                                }
+                                for notifier in self.notifier.read().iter() {
+                                    notifier.notify();
+                                }
+                                self.jobs = jobs
+                                    .iter()
+                                    .map(|job| JobStatus {
+                                        from: job.from,
+                                        to: job.to,
+                                        target: hex::encode(&job.target),
+                                        difficulty: pow::difficulty(&job.target),
+                                        txs_size: job.txs.len(),
+                                        age: 0,
+                                    })
+                                    .collect();
+                                self.jobs_time = Some(Instant::now());
                                 //dispatch job
                                 for job in jobs {
-                                    let task = Task::new().with_job(job);
+                                    self.counter.lock().job_received(&job);
+                                    Event::JobReceived {
+                                        from: job.from,
+                                        to: job.to,
+                                        target: hex::encode(&job.target),
+                                        difficulty: pow::difficulty(&job.target),
+                                        txs_size: job.txs.len(),
+                                    }
+                                    .emit();
+                                    let task = Task::new()
+                                        .with_job(job)
+                                        .with_share_target(self.share_target.clone())
+                                        .with_nonce_prefix(self.nonce_prefix)
+                                        .with_groups(self.groups.get());
                                     self.sender
                                         .as_ref()
                                         .unwrap()
@@ -158,19 +660,460 @@ impl Scheduler {
                                 }
                             }
                             Body::SubmitResult(ret) => {
-                                let key = format!("{}-{}", ret.from, ret.to);
-                                info!("SubmitResult info: {}", key);
+                                //节点按提交顺序返回同一条链的结果
+                                let index = self
+                                    .pending_tasks
+                                    .iter()
+                                    .position(|val| val.chain == (ret.from, ret.to));
+                                let submitted =
+                                    index.and_then(|index| self.pending_tasks.remove(index));
+                                //找不到对应的提交时按区块计
+                                let share =
+                                    submitted.as_ref().is_some_and(|val| val.block.is_none());
+                                self.counter
+                                    .lock()
+                                    .submit_result(ret.from, ret.to, ret.status, share);
+                                let mut round_trip = None;
+                                if let Some(submitted) = submitted {
+                                    round_trip = Some(submitted.time.elapsed());
+                                    self.counter
+                                        .lock()
+                                        .submit_round_trip(submitted.time.elapsed());
+                                    if let Some(block) = submitted.block {
+                                        self.hook.fire(BlockEvent {
+                                            accepted: ret.status,
+                                            timestamp: chrono::Utc::now().timestamp_millis(),
+                                            ..block
+                                        });
+                                    }
+                                }
+                                Event::SubmitResult {
+                                    from: ret.from,
+                                    to: ret.to,
+                                    accepted: ret.status,
+                                    round_trip: round_trip.map(|val| val.as_secs_f64()),
+                                }
+                                .emit();
+                            }
+                            Body::NoncePrefix(prefix) => {
+                                info!("nonce prefix assigned by proxy: {:08x}", prefix);
+                                self.nonce_prefix = prefix;
                             }
                             _ => unreachable!(),
                         }
                     }
-                    Unit::TASK(task) => {
-                        self.pending_tasks.push((task.clone(), false));
-                        counter.add(task);
-                        counter.interval_print();
+                    Unit::TASK(task) => self.counter.lock().add(task),
+                    Unit::Submitted(submitted) => {
+                        if self.pending_tasks.len() >= constant::MAX_PENDING_SUBMITS {
+                            if let Some(oldest) = self.pending_tasks.pop_front() {
+                                warn!(
+                                    "no result for task {} on chain {}-{}, stop waiting",
+                                    oldest.task_id, oldest.chain.0, oldest.chain.1
+                                );
+                            }
+                        }
+                        self.pending_tasks.push_back(submitted);
+                    }
+                    Unit::Connected => self.pending_tasks.clear(),
+                    Unit::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        }
+    }
+
+    //检查新任务，按策略丢弃有问题的任务、整批丢弃并重连或退出
+    fn sanitize(&mut self, jobs: Vec<Job>) -> Result<Option<Vec<Job>>, String> {
+        //按这批任务推断的分组数检查，通过后才更新
+        let groups = self.groups.infer(jobs.len());
+        let checker = Checker {
+            groups,
+            min_zero_bits: self.conf.job_check.min_zero_bits(self.conf.network),
+        };
+        let problems = checker.check(&jobs);
+        if problems.is_empty() {
+            return Ok(Some(jobs));
+        }
+        //同一问题的链合并到一起
+        let mut details: Vec<(String, Vec<String>)> = vec![];
+        for (index, problem) in problems.iter() {
+            self.counter.lock().rejected_job(problem.reason());
+            let chain = format!("{}-{}", jobs[*index].from, jobs[*index].to);
+            let problem = problem.to_string();
+            match details.iter_mut().find(|(val, _)| *val == problem) {
+                Some((_, chains)) => chains.push(chain),
+                None => details.push((problem, vec![chain])),
+            }
+        }
+        let details: Vec<String> = details
+            .iter()
+            .map(|(problem, chains)| format!("{} ({})", problem, chains.join(" ")))
+            .collect();
+        let summary = format!(
+            "{} of {} jobs failed the checks: {}",
+            problems.len(),
+            jobs.len(),
+            details.join(", ")
+        );
+        match self.conf.job_check.policy {
+            //分组数随任务数变化时，不凭有问题的批次推断
+            JobPolicy::Skip if groups != self.groups.get() => {
+                warn!(
+                    "{}, discard them and keep {} groups",
+                    summary,
+                    self.groups.get()
+                );
+                Ok(None)
+            }
+            JobPolicy::Skip => {
+                warn!("{}, skip them", summary);
+                let rejected: Vec<usize> = problems.iter().map(|(index, _)| *index).collect();
+                Ok(Some(
+                    jobs.into_iter()
+                        .enumerate()
+                        .filter(|(index, _)| !rejected.contains(index))
+                        .map(|(_, job)| job)
+                        .collect(),
+                ))
+            }
+            JobPolicy::Reconnect => {
+                //重连后节点立即下发任务，限制重连频率
+                let delay = Duration::from_secs(constant::RECONNECT_DELAY);
+                if self.rejected_time.is_none_or(|val| val.elapsed() >= delay) {
+                    error!("{}, reconnect", summary);
+                    self.rejected_time = Some(Instant::now());
+                    if let Some(reconnect) = self.reconnect.as_ref() {
+                        let _ = reconnect.try_send(());
                     }
+                } else {
+                    error!("{}, discard them", summary);
+                }
+                Ok(None)
+            }
+            JobPolicy::Abort => Err(format!("{}, abort", summary)),
+        }
+    }
+
+    fn command(&mut self, command: Command) -> Reply {
+        match command {
+            Command::Status => {}
+            Command::Pause => {
+                self.paused = true;
+                self.notifier.read().iter().for_each(|val| val.pause());
+                info!("mining paused");
+            }
+            Command::Resume => {
+                self.paused = false;
+                self.notifier.read().iter().for_each(|val| val.resume());
+                info!("mining resumed");
+            }
+            Command::SetWorkers { count } => self.scale(count)?,
+            Command::AddWorkers { count } => {
+                let count = self.notifier.read().len() + count;
+                self.scale(count)?
+            }
+            Command::RemoveWorkers { count } => {
+                let count = self.notifier.read().len().saturating_sub(count);
+                self.scale(count)?
+            }
+            Command::SetBackend {
+                miner_type,
+                hash_impl,
+            } => {
+                let spawner = self.spawner.as_mut().ok_or("workers can not be changed")?;
+                if let Some(miner_type) = miner_type.filter(|val| val != "cpu") {
+                    return Err(format!(
+                        "backend {} has no mining implementation",
+                        miner_type
+                    ));
+                }
+                if let Some(hash_impl) = hash_impl.filter(|val| *val != spawner.conf.hash_impl) {
+                    spawner.conf.hash_impl = hash_impl;
+                    self.respawn()?;
+                    info!("switched workers to {}", hash_impl);
+                }
+            }
+            Command::Reconnect => {
+                let reconnect = self
+                    .reconnect
+                    .as_ref()
+                    .ok_or("reconnect is not supported")?;
+                if !self.counter.lock().is_connected() {
+                    return Err("not connected to node".to_string());
+                }
+                let _ = reconnect.try_send(());
+                info!("reconnect requested");
+            }
+            Command::ReloadConfig => {
+                let reload = self.reload.clone().ok_or("no config to reload")?;
+                let conf = reload().map_err(|err| format!("reload config error: {}", err))?;
+                self.apply(conf)?;
+            }
+        }
+        Ok(self.status())
+    }
+
+    /// Applies the settings changed since the last loaded config that do not
+    /// need a restart, and logs the ones that do.
+    fn apply(&mut self, conf: config::Config) -> Result<(), String> {
+        let (applied, restart): (Vec<String>, Vec<String>) = self
+            .conf
+            .changes(&conf)
+            .into_iter()
+            .partition(|val| config::reloadable(val));
+        let changed = |name: &str| {
+            applied
+                .iter()
+                .any(|val| val == name || val.starts_with(&format!("{}.", name)))
+        };
+        if changed("stats_interval") {
+            self.counter.lock().set_interval(conf.stats_interval);
+        }
+        if changed("notify") {
+            self.hook = Hook::new(conf.notify.clone());
+        }
+        if changed("share_target") {
+            //下一批任务生效
+            self.share_target = conf.share_target.clone();
+        }
+        //只改动变了的设置，不覆盖运行中 set_backend 的选择
+        if let Some(spawner) = self.spawner.as_mut() {
+            if changed("throttle") {
+                spawner.conf.throttle.cpu = conf.throttle.cpu;
+                spawner.conf.throttle.hash_rate = conf.throttle.hash_rate;
+                spawner.rebalance(self.notifier.read().len());
+            }
+            if changed("mining_steps") {
+                spawner.conf.mining_steps = conf.mining_steps;
+            }
+            if changed("hash_impl") {
+                spawner.conf.hash_impl = conf.hash_impl;
+            }
+        }
+        //出错也继续应用其余设置，最后一起返回
+        let mut errors = vec![];
+        if changed("hash_impl") || changed("mining_steps") {
+            errors.extend(self.respawn().err());
+        }
+        if changed("worker_num") {
+            errors.extend(self.scale(conf.worker_num).err());
+        }
+        if changed("ip") || changed("port") || changed("endpoints") {
+            *self.endpoint.write() = conf.nodes();
+            //备用节点在下次断线时生效，不打断当前连接
+            if changed("ip") || changed("port") {
+                if let Some(reconnect) = self.reconnect.as_ref() {
+                    let _ = reconnect.try_send(());
                 }
             }
         }
+        if applied.is_empty() {
+            info!("config reloaded, nothing to apply");
+        } else {
+            info!("config reloaded, applied {}", applied.join(", "));
+        }
+        if !restart.is_empty() {
+            warn!("config changed, restart to apply {}", restart.join(", "));
+        }
+        //最后调整日志级别，上面的日志按原级别输出
+        if changed("log.level") {
+            if let Err(err) = logger::set_level(conf.log.level.as_deref()) {
+                warn!("{}", err);
+            }
+        }
+        self.conf = conf;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+
+    //新线程先启动，再停掉旧线程
+    fn respawn(&mut self) -> Result<(), String> {
+        let count = self.notifier.read().len();
+        let old = std::mem::take(&mut *self.notifier.write());
+        self.scale(count)?;
+        self.retire(old);
+        Ok(())
+    }
+
+    //增减工作线程到 count 个
+    fn scale(&mut self, count: usize) -> Result<(), String> {
+        if count > config::max_workers() {
+            return Err(format!(
+                "at most {} workers, got {}",
+                config::max_workers(),
+                count
+            ));
+        }
+        let spawner = self.spawner.as_mut().ok_or("workers can not be changed")?;
+        let mut notifiers = self.notifier.write();
+        while notifiers.len() < count {
+            let notifier = spawner.spawn(notifiers.len());
+            if self.paused {
+                notifier.pause();
+            }
+            notifiers.push(notifier);
+        }
+        let removed = notifiers.split_off(count);
+        spawner.rebalance(count);
+        drop(notifiers);
+        self.retire(removed);
+        info!("running {} workers", count);
+        Ok(())
+    }
+
+    //停止工作线程，先收走已上报的计算次数
+    fn retire(&self, notifiers: Vec<Arc<Notifier>>) {
+        let mut counter = self.counter.lock();
+        for notifier in notifiers {
+            notifier.stop();
+            counter.record_hashes(notifier.worker_id(), notifier.take_hashes());
+            counter.remove_worker(notifier.worker_id());
+        }
+    }
+
+    fn status(&self) -> Status {
+        let age = self.jobs_time.map_or(0, |val| val.elapsed().as_secs());
+        let counter = self.counter.lock();
+        Status {
+            paused: self.paused,
+            connected: counter.is_connected(),
+            hash_rate: counter.hash_rate(),
+            miner_type: self
+                .spawner
+                .as_ref()
+                .map_or("cpu".to_string(), |val| val.conf.miner_type.clone()),
+            hash_impl: self
+                .spawner
+                .as_ref()
+                .map_or(Default::default(), |val| val.conf.hash_impl),
+            workers: self
+                .notifier
+                .read()
+                .iter()
+                .map(|val| WorkerStatus {
+                    id: val.worker_id().to_string(),
+                    state: val.state().to_string(),
+                })
+                .collect(),
+            jobs: self
+                .jobs
+                .iter()
+                .map(|val| JobStatus { age, ..val.clone() })
+                .collect(),
+        }
+    }
+
+    //汇总矿工在 MINING_STEPS 检查点上报的计算次数
+    fn collect_progress(&self) {
+        let mut counter = self.counter.lock();
+        for notifier in self.notifier.read().iter() {
+            counter.record_hashes(notifier.worker_id(), notifier.take_hashes());
+        }
+        counter.interval_print();
+    }
+}
+
+//没有控制通道时永远等待
+async fn recv_control(
+    control: &mut Option<mpsc::Receiver<control::Request>>,
+) -> Option<control::Request> {
+    match control.as_mut() {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+//SIGHUP 与 reload_config 命令相同
+async fn reload_on_hangup(control: Control) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            error!("listen SIGHUP error {}", err);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("SIGHUP received, reloading config");
+        if let Err(err) = control.send(Command::ReloadConfig).await {
+            error!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scheduler;
+    use crate::config::{self, JobPolicy};
+    use crate::model::Job;
+    use crate::network::{self, Groups};
+    use crate::pow::HashImpl;
+
+    fn jobs(groups: u32) -> Vec<Job> {
+        (0..groups * groups)
+            .map(|index| Job {
+                from: index / groups,
+                to: index % groups,
+                header: vec![1; network::header_size(groups)],
+                txs: vec![],
+                target: vec![0x0f; 28],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scale_limit() {
+        let mut scheduler = Scheduler::new();
+        let err = scheduler.scale(1_000_000).unwrap_err();
+        assert_eq!(
+            err,
+            format!("at most {} workers, got 1000000", config::max_workers())
+        );
+        // within the limit the count is accepted, this scheduler has no workers
+        assert_eq!(
+            scheduler.scale(1).unwrap_err(),
+            "workers can not be changed"
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut scheduler = Scheduler::new().with_groups(Groups::new(None));
+        // 4 jobs of a 2 group network, one chain twice
+        let mut batch = jobs(2);
+        batch[3].from = 0;
+        assert_eq!(scheduler.sanitize(batch).unwrap(), None);
+        assert_eq!(scheduler.groups.get(), 4);
+        assert_eq!(scheduler.sanitize(jobs(2)).unwrap().unwrap().len(), 4);
+
+        let mut batch = jobs(4);
+        batch[0].target = vec![0; 28];
+        assert_eq!(
+            scheduler.sanitize(batch.clone()).unwrap().unwrap().len(),
+            15
+        );
+        let mut conf = config::Config::default();
+        conf.job_check.policy = JobPolicy::Abort;
+        scheduler = scheduler.with_config(conf);
+        assert!(scheduler.sanitize(batch).unwrap_err().ends_with(", abort"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut scheduler = Scheduler::new();
+        let mut conf = config::Config::default();
+        conf.worker_num = 1;
+        conf.stats_interval = 30;
+        conf.hash_impl = HashImpl::Blake3Merkle;
+        // this scheduler has no workers, scaling fails but the rest is
+        // applied and the new config is kept
+        let err = scheduler.apply(conf).unwrap_err();
+        assert_eq!(err, "workers can not be changed");
+        assert_eq!(scheduler.conf.stats_interval, 30);
+        assert_eq!(scheduler.conf.worker_num, 1);
+        assert!(scheduler.apply(scheduler.conf.clone()).is_ok());
     }
 }
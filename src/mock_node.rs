@@ -0,0 +1,223 @@
+use crate::connection::{self, Peer, Writer};
+use crate::model::{Blob, ClientMessage, Job, Jobs, Message, SubmitReq, SubmitResult};
+use crate::{constant, network, pow, Frame};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+//每条链平均 256 * 16 次哈希出块
+pub const EASY_TARGET: &str = "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+
+/// A fake Alephium node speaking the miner protocol, for tests and demos.
+/// It hands out jobs with random headers for every chain, checks the PoW of
+/// the submitted blocks and replaces the job of a chain once it is mined.
+pub struct MockNode {
+    listen: String,
+    target: Blob,
+    interval: u64,       //刷新全部任务的间隔，秒
+    blocks: Option<u64>, //接受指定数量的区块后退出
+    groups: u32,         //分组数，每对分组一条链
+}
+
+impl MockNode {
+    pub fn new(listen: String) -> MockNode {
+        MockNode {
+            listen,
+            target: hex::decode(EASY_TARGET).unwrap(),
+            interval: 10,
+            blocks: None,
+            groups: constant::GROUP_NUMS,
+        }
+    }
+
+    pub fn with_target(mut self, target: Blob) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_blocks(mut self, blocks: Option<u64>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: u32) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub async fn work(&mut self) {
+        let listener = TcpListener::bind(&self.listen).await.unwrap();
+        info!("mock node listening on {}", self.listen);
+        self.run(listener).await;
+    }
+
+    /// Serves miners on `listener`, returns once `blocks` blocks are accepted.
+    pub async fn run(&self, listener: TcpListener) {
+        let (tx, mut rx) = mpsc::channel::<Peer>(1024);
+        connection::serve(listener, tx);
+
+        let mut clients: HashMap<u32, Writer> = HashMap::new();
+        let mut jobs = self.jobs();
+        let mut accepted = 0;
+        let mut interval = tokio::time::interval(Duration::from_secs(self.interval.max(1)));
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    jobs = self.jobs();
+                    broadcast(&mut clients, &jobs).await;
+                }
+                peer = rx.recv() => match peer {
+                    Some(Peer::Connected(id, mut w)) => {
+                        if send(&mut w, Message::jobs(jobs.clone())).await {
+                            clients.insert(id, w);
+                        }
+                    }
+                    Some(Peer::Request(id, ClientMessage::SubmitReq(req))) => {
+                        let ret = verify(&jobs, &req, self.groups);
+                        info!(
+                            "miner {} submit block {}-{}: {}",
+                            id, ret.from, ret.to, ret.status
+                        );
+                        let status = ret.status;
+                        let chain = (ret.from, ret.to);
+                        if let Some(w) = clients.get_mut(&id) {
+                            send(w, Message::submit_result(ret)).await;
+                        }
+                        if status {
+                            accepted += 1;
+                            if self.blocks.is_some_and(|blocks| accepted >= blocks) {
+                                info!("mock node accepted {} blocks, exit", accepted);
+                                break;
+                            }
+                            //出块后更新该链的任务
+                            for job in jobs.iter_mut() {
+                                if (job.from, job.to) == chain {
+                                    *job = self.job(chain.0, chain.1);
+                                }
+                            }
+                            broadcast(&mut clients, &jobs).await;
+                        }
+                    }
+                    Some(Peer::Request(id, ClientMessage::Hello(hello))) => {
+                        info!("miner {} is rig {}", id, hello.rig);
+                    }
+                    Some(Peer::Disconnected(id)) => {
+                        clients.remove(&id);
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+
+    fn jobs(&self) -> Jobs {
+        let mut jobs = vec![];
+        for from in 0..self.groups {
+            for to in 0..self.groups {
+                jobs.push(self.job(from, to));
+            }
+        }
+        jobs
+    }
+
+    fn job(&self, from: u32, to: u32) -> Job {
+        Job {
+            from,
+            to,
+            header: (0..network::header_size(self.groups))
+                .map(|_| rand::random::<u8>())
+                .collect(),
+            txs: (0..64).map(|_| rand::random::<u8>()).collect(),
+            target: self.target.clone(),
+        }
+    }
+}
+
+fn verify(jobs: &Jobs, req: &SubmitReq, groups: u32) -> SubmitResult {
+    let hash = pow::hash(&req.nonce, &req.header);
+    let (from, to) = pow::chain_index(&hash, groups);
+    let status = req.nonce.len() == 24
+        && jobs.iter().any(|job| {
+            job.header == req.header
+                && job.txs == req.txs
+                && (job.from, job.to) == (from, to)
+                && pow::check_target(&hash, &job.target)
+        });
+    SubmitResult { from, to, status }
+}
+
+async fn send(w: &mut Writer, msg: Message) -> bool {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    let data = bincode::encode_to_vec(msg, option).expect("encode_to_vec msg error");
+    match w.write_frame(&Frame::Bulk(data)).await {
+        Ok(_) => true,
+        Err(err) => {
+            error!("mock node write_frame error {}", err);
+            false
+        }
+    }
+}
+
+async fn broadcast(clients: &mut HashMap<u32, Writer>, jobs: &Jobs) {
+    let mut closed = vec![];
+    for (id, w) in clients.iter_mut() {
+        if !send(w, Message::jobs(jobs.clone())).await {
+            closed.push(*id);
+        }
+    }
+    for id in closed {
+        clients.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, MockNode};
+    use crate::model::SubmitReq;
+    use crate::pow;
+
+    #[test]
+    fn test_verify() {
+        let node = MockNode::new("127.0.0.1:0".to_string());
+        let jobs = node.jobs();
+        assert_eq!(jobs.len(), 16);
+        let job = &jobs[6];
+        let mut nonce = vec![0u8; 24];
+        let mut i: u64 = 0;
+        // brute force a valid nonce for the easy target
+        let req = loop {
+            i += 1;
+            nonce[16..].copy_from_slice(&i.to_be_bytes());
+            let hash = pow::hash(&nonce, &job.header);
+            if pow::chain_index(&hash, 4) == (job.from, job.to)
+                && pow::check_target(&hash, &job.target)
+            {
+                break SubmitReq {
+                    nonce: nonce.clone(),
+                    header: job.header.clone(),
+                    txs: job.txs.clone(),
+                };
+            }
+        };
+        let ret = verify(&jobs, &req, 4);
+        assert!(ret.status);
+        assert_eq!((ret.from, ret.to), (job.from, job.to));
+
+        let mut stale = req.clone();
+        stale.header[0] ^= 1;
+        assert!(!verify(&jobs, &stale, 4).status);
+
+        let node = MockNode::new("127.0.0.1:0".to_string()).with_groups(2);
+        assert_eq!(node.jobs().len(), 4);
+    }
+}
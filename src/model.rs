@@ -41,11 +41,21 @@ pub struct SubmitResult {
     pub status: bool,
 }
 
+/// Who is mining, sent to pools and proxies. The node protocol has no
+/// message for it.
+#[derive(Encode, Decode, PartialEq, Debug, Clone, Default)]
+pub struct Hello {
+    pub rig: String,
+    pub addresses: Vec<String>, //第 g 个属于分组 g，可以为空
+}
+
 #[derive(Debug, Clone)]
 pub enum Body {
     Jobs(Jobs),
     SubmitReq(SubmitReq),
     SubmitResult(SubmitResult),
+    NoncePrefix(u32), //代理分配给下游矿工的 nonce 前缀
+    Hello(Hello),
 }
 
 impl From<Message> for Body {
@@ -75,7 +85,7 @@ impl Default for Body {
 #[derive(Debug, Default, Clone)]
 pub struct Message {
     len: u32, //len = len(kind) + len(body)[👌];len = len + len(kind) + len(body)[🤯];
-    kind: u8, //1 = Jobs ; 0 = SubmitResult
+    kind: u8, //节点发送 0 = Jobs ; 1 = SubmitResult ; 2 = NoncePrefix，矿工发送 0 = SubmitReq ; 1 = Hello
     body: Body,
 }
 
@@ -87,6 +97,68 @@ impl Message {
             body: Body::SubmitReq(req),
         }
     }
+
+    pub fn jobs(jobs: Jobs) -> Self {
+        Message {
+            len: 0,
+            kind: 0,
+            body: Body::Jobs(jobs),
+        }
+    }
+
+    pub fn submit_result(ret: SubmitResult) -> Self {
+        Message {
+            len: 0,
+            kind: 1,
+            body: Body::SubmitResult(ret),
+        }
+    }
+
+    pub fn nonce_prefix(prefix: u32) -> Self {
+        Message {
+            len: 0,
+            kind: 2,
+            body: Body::NoncePrefix(prefix),
+        }
+    }
+
+    pub fn is_nonce_prefix(&self) -> bool {
+        matches!(self.body, Body::NoncePrefix(_))
+    }
+
+    pub fn hello(hello: Hello) -> Self {
+        Message {
+            len: 0,
+            kind: 1,
+            body: Body::Hello(hello),
+        }
+    }
+}
+
+/// Messages sent from a miner to the node. `Message` only decodes the node
+/// side of the protocol, servers (proxy) decode their requests with this.
+#[derive(Debug, Clone)]
+pub enum ClientMessage {
+    SubmitReq(SubmitReq),
+    Hello(Hello), //kind 1
+}
+
+impl Decode for ClientMessage {
+    fn decode<D: Decoder>(mut decoder: D) -> Result<Self, DecodeError> {
+        let option = bincode::config::Configuration::standard()
+            .with_big_endian()
+            .with_no_limit()
+            .with_fixed_int_encoding();
+        let _size = u32::decode(&mut decoder)?;
+        let kind = u8::decode(&mut decoder)?;
+        let req = Blob::decode(&mut decoder)?;
+        if kind == 1 {
+            let (hello, _) = bincode::decode_from_slice::<Hello, _>(req.as_slice(), option)?;
+            return Ok(ClientMessage::Hello(hello));
+        }
+        let (req, _) = bincode::decode_from_slice::<SubmitReq, _>(req.as_slice(), option)?;
+        Ok(ClientMessage::SubmitReq(req))
+    }
 }
 
 impl Encode for Message {
@@ -120,6 +192,21 @@ impl Encode for Message {
                 kind.encode(&mut encoder)?;
                 ret.encode(encoder)
             }
+            Body::Hello(hello) => {
+                let req = bincode::encode_to_vec(hello, option)?;
+                size += 4 + req.len() as u32;
+                kind = 1;
+                size.encode(&mut encoder)?;
+                kind.encode(&mut encoder)?;
+                req.encode(&mut encoder)
+            }
+            Body::NoncePrefix(prefix) => {
+                size += 4;
+                kind = 2;
+                size.encode(&mut encoder)?;
+                kind.encode(&mut encoder)?;
+                prefix.encode(encoder)
+            }
         }
     }
 }
@@ -135,6 +222,13 @@ impl Decode for Message {
                 kind,
                 body: Body::Jobs(job),
             })
+        } else if kind == 2 {
+            let prefix = u32::decode(&mut decoder)?;
+            Ok(Message {
+                len: size,
+                kind,
+                body: Body::NoncePrefix(prefix),
+            })
         } else {
             let ret = SubmitResult::decode(&mut decoder)?;
             Ok(Message {
@@ -0,0 +1,244 @@
+//! Shape of the network being mined: its group count, configured, taken from
+//! a named network profile or learned from the jobs the node sends, one job
+//! per chain.
+
+use crate::config::Config;
+use crate::constant;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest group count. The chain of a block hash is picked by its last two
+/// bytes, see `pow::chain_index`.
+pub const MAX_GROUPS: u32 = 16;
+
+/// Leading zero bits of the easiest target on any network, a target of
+/// half the hash space or more is a broken node.
+pub const MIN_ZERO_BITS: u32 = 1;
+
+/// Length of a block header without its nonce in a network of `groups`
+/// groups: version(1) + deps count(1) + deps(32 * (2 * groups - 1)) +
+/// depStateHash(32) + txsHash(32) + timestamp(8) + compact target(4).
+pub fn header_size(groups: u32) -> usize {
+    2 + 32 * (2 * groups as usize - 1) + 72 + 4
+}
+
+/// Named network selected with `--network`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet, //本地开发网络
+}
+
+/// What a miner expects from the nodes of a network.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub port: u16,          //节点挖矿端口
+    pub groups: u32,        //分组数
+    pub min_zero_bits: u32, //任务目标至少的前导零位数
+}
+
+impl Profile {
+    /// Jobs the node sends at once, one per chain.
+    pub fn jobs(&self) -> usize {
+        (self.groups * self.groups) as usize
+    }
+}
+
+impl Network {
+    pub const ALL: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Devnet];
+
+    pub fn profile(&self) -> Profile {
+        match self {
+            Network::Mainnet => Profile {
+                port: 10973,
+                groups: 4,
+                min_zero_bits: 37,
+            },
+            //测试网和开发网难度波动大，只拦截明显错误的目标
+            Network::Testnet => Profile {
+                port: 10973,
+                groups: 4,
+                min_zero_bits: MIN_ZERO_BITS,
+            },
+            Network::Devnet => Profile {
+                port: 20973,
+                groups: 4,
+                min_zero_bits: MIN_ZERO_BITS,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet => write!(f, "testnet"),
+            Network::Devnet => write!(f, "devnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            _ => anyhow::bail!("unknown network {}, expected mainnet, testnet or devnet", s),
+        }
+    }
+}
+
+/// Group count of a network sending `jobs` jobs, one per chain.
+pub fn groups_of(jobs: usize) -> Option<u32> {
+    (1..=MAX_GROUPS).find(|groups| (groups * groups) as usize == jobs)
+}
+
+/// The group count in use: the configured one or the network profile's,
+/// otherwise the one inferred from the latest jobs, mainnet's until the
+/// first jobs arrive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Groups {
+    configured: Option<u32>, //配置指定时不再推断
+    network: Option<Network>,
+    current: u32,
+    mismatch: Option<usize>, //上次告警的任务数，相同的不重复告警
+}
+
+impl Default for Groups {
+    fn default() -> Self {
+        Groups::new(None)
+    }
+}
+
+impl Groups {
+    pub fn new(configured: Option<u32>) -> Groups {
+        Groups {
+            configured,
+            network: None,
+            current: configured.unwrap_or(constant::GROUP_NUMS),
+            mismatch: None,
+        }
+    }
+
+    /// Groups set in `conf`, or those of its network profile.
+    pub fn from_config(conf: &Config) -> Groups {
+        let profile = conf.network.map(|val| val.profile().groups);
+        Groups {
+            network: conf.network,
+            ..Groups::new(conf.groups.or(profile))
+        }
+    }
+
+    pub fn get(&self) -> u32 {
+        self.current
+    }
+
+    /// Checks a batch of `jobs` jobs against the group count, returns the
+    /// new count when it was inferred from them and changed.
+    pub fn update(&mut self, jobs: usize) -> Option<u32> {
+        let inferred = groups_of(jobs);
+        let mismatch = match self.configured {
+            Some(configured) => inferred != Some(configured),
+            None => inferred.is_none(),
+        };
+        if !mismatch {
+            self.mismatch = None;
+        } else if self.mismatch != Some(jobs) {
+            self.mismatch = Some(jobs);
+            match self.network {
+                //选错网络时提交的区块都会被拒绝
+                Some(network) => error!(
+                    "{} jobs do not match {} groups ({} chains) of {}, is the node on another network?",
+                    jobs,
+                    self.current,
+                    self.current * self.current,
+                    network
+                ),
+                None => warn!(
+                    "{} jobs do not match {} groups ({} chains), {}",
+                    jobs,
+                    self.current,
+                    self.current * self.current,
+                    if self.configured.is_some() {
+                        "check the configured groups"
+                    } else {
+                        "keep the group count"
+                    }
+                ),
+            }
+        }
+        let groups = self.infer(jobs);
+        if groups == self.current {
+            return None;
+        }
+        info!("{} jobs, the network has {} groups", jobs, groups);
+        self.current = groups;
+        Some(groups)
+    }
+
+    /// Group count a batch of `jobs` jobs is checked against, the one
+    /// `update` switches to.
+    pub fn infer(&self, jobs: usize) -> u32 {
+        match (self.configured, groups_of(jobs)) {
+            (None, Some(groups)) => groups,
+            _ => self.current,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{groups_of, header_size, Groups, Network};
+    use crate::config::Config;
+
+    #[test]
+    fn test_groups() {
+        assert_eq!(groups_of(16), Some(4));
+        assert_eq!(groups_of(9), Some(3));
+        assert_eq!(groups_of(1), Some(1));
+        assert_eq!(groups_of(12), None);
+        assert_eq!(groups_of(289), None);
+
+        let mut groups = Groups::default();
+        assert_eq!(groups.get(), 4);
+        assert_eq!(groups.update(16), None);
+        assert_eq!(groups.update(9), Some(3));
+        assert_eq!(groups.get(), 3);
+        // a partial batch keeps the count
+        assert_eq!(groups.update(5), None);
+        assert_eq!(groups.get(), 3);
+
+        let mut groups = Groups::new(Some(2));
+        assert_eq!(groups.update(16), None);
+        assert_eq!(groups.get(), 2);
+
+        // a profile fixes the group count, the configured one still wins
+        let mut conf = Config {
+            network: Some(Network::Devnet),
+            ..Default::default()
+        };
+        let mut groups = Groups::from_config(&conf);
+        assert_eq!(groups.update(9), None);
+        assert_eq!(groups.get(), 4);
+        conf.groups = Some(3);
+        assert_eq!(Groups::from_config(&conf).get(), 3);
+    }
+
+    #[test]
+    fn test_profile() {
+        for network in Network::ALL {
+            assert_eq!(network.to_string().parse::<Network>().unwrap(), network);
+            assert_eq!(network.profile().jobs(), 16);
+        }
+        assert!("regtest".parse::<Network>().is_err());
+        assert_eq!(Network::Devnet.profile().port, 20973);
+        assert_eq!(header_size(4), 302);
+    }
+}
@@ -1 +1,163 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
+/// Blake3 implementation used for mining, both give the same hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashImpl {
+    #[default]
+    Blake3, //blake3 crate，带 SIMD
+    Blake3Merkle, //仓库内的 blake3-merkle 参考实现
+}
+
+impl HashImpl {
+    pub const ALL: [HashImpl; 2] = [HashImpl::Blake3, HashImpl::Blake3Merkle];
+}
+
+impl fmt::Display for HashImpl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashImpl::Blake3 => write!(f, "blake3"),
+            HashImpl::Blake3Merkle => write!(f, "blake3-merkle"),
+        }
+    }
+}
+
+impl FromStr for HashImpl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(HashImpl::Blake3),
+            "blake3-merkle" => Ok(HashImpl::Blake3Merkle),
+            _ => anyhow::bail!(
+                "unknown hash implementation {}, expected blake3 or blake3-merkle",
+                s
+            ),
+        }
+    }
+}
+
+/// Blake3 hashes computed for each nonce tried, the unit of hash counts.
+pub const HASHES_PER_NONCE: u64 = 2;
+
+/// Block hash: blake3 applied twice over `nonce ++ header`.
+pub fn hash(nonce: &[u8], header: &[u8]) -> [u8; 32] {
+    hash_with(HashImpl::Blake3, nonce, header)
+}
+
+pub fn hash_with(imp: HashImpl, nonce: &[u8], header: &[u8]) -> [u8; 32] {
+    match imp {
+        HashImpl::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(nonce);
+            hasher.update(header);
+            let hash1 = hasher.finalize();
+            *blake3::hash(hash1.as_bytes()).as_bytes()
+        }
+        HashImpl::Blake3Merkle => {
+            let mut hash = [0u8; 32];
+            let mut hasher = blake3_merkle::Hasher::new();
+            hasher.update(nonce);
+            hasher.update(header);
+            hasher.finalize(&mut hash);
+            let mut hasher = blake3_merkle::Hasher::new();
+            hasher.update(&hash);
+            hasher.finalize(&mut hash);
+            hash
+        }
+    }
+}
+
+/// The `(from, to)` chain a block hash belongs to in a network of `groups`
+/// groups, from its last two bytes like the node's `ChainIndex.from`.
+pub fn chain_index(hash: &[u8], groups: u32) -> (u32, u32) {
+    let big_index = ((hash[30] as u32) << 8 | hash[31] as u32) % (groups * groups);
+    (big_index / groups, big_index % groups)
+}
+
+/// Whether `hash` is at most `target`, the target may omit leading zeros.
+/// A target longer than the hash is never met.
+pub fn check_target(hash: &[u8], target: &[u8]) -> bool {
+    let zero_len = match hash.len().checked_sub(target.len()) {
+        Some(val) => val,
+        None => return false,
+    };
+    let (zero_hash, non_zero_hash) = hash.split_at(zero_len);
+    if zero_hash.iter().any(|zero| *zero != 0) {
+        return false;
+    }
+    non_zero_hash <= target
+}
+
+/// Leading zero bits of `target` as a 32 byte number.
+pub fn zero_bits(target: &[u8]) -> u32 {
+    let omitted = 32u32.saturating_sub(target.len() as u32) * 8;
+    match target.iter().position(|byte| *byte != 0) {
+        Some(index) => omitted + index as u32 * 8 + target[index].leading_zeros(),
+        None => omitted + target.len() as u32 * 8,
+    }
+}
+
+/// Expected number of hashes needed to find a hash below `target`,
+/// i.e. `2^256 / (target + 1)`.
+pub fn difficulty(target: &[u8]) -> f64 {
+    let value = target
+        .iter()
+        .fold(0f64, |acc, byte| acc * 256f64 + *byte as f64);
+    2f64.powi(256) / (value + 1f64)
+}
+
+/// Expected number of hashes behind one share: the hash has to meet the
+/// target and also land on the job's chain, one of `groups * groups`.
+/// Counted like the local hash count, `HASHES_PER_NONCE` per nonce.
+pub fn share_hashes(target: &[u8], groups: u32) -> f64 {
+    difficulty(target) * (groups * groups) as f64 * HASHES_PER_NONCE as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chain_index, difficulty, hash, hash_with, share_hashes, zero_bits, HashImpl};
+
+    #[test]
+    fn test_difficulty() {
+        let target =
+            hex::decode("00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff")
+                .unwrap();
+        assert!((difficulty(&target) - 256f64).abs() < 1e-6);
+        // leading zeros of the target are optional
+        assert_eq!(difficulty(&target), difficulty(&target[1..]));
+        assert!((share_hashes(&target, 4) - 256f64 * 16f64 * 2f64).abs() < 1e-6);
+        assert!((share_hashes(&target, 2) - 256f64 * 4f64 * 2f64).abs() < 1e-6);
+        assert_eq!(zero_bits(&target), 8);
+        assert_eq!(zero_bits(&target[1..]), 8);
+        assert_eq!(zero_bits(&[0x07]), 253);
+    }
+
+    #[test]
+    fn test_chain_index() {
+        let mut hash = [0u8; 32];
+        hash[31] = 0x1e;
+        assert_eq!(chain_index(&hash, 4), (3, 2));
+        assert_eq!(chain_index(&hash, 2), (1, 0));
+        assert_eq!(chain_index(&hash, 3), (1, 0));
+        assert_eq!(chain_index(&hash, 1), (0, 0));
+        // 0x011e = 286, group counts that are not powers of two see the
+        // second to last byte
+        hash[30] = 0x01;
+        assert_eq!(chain_index(&hash, 4), (3, 2));
+        assert_eq!(chain_index(&hash, 3), (2, 1));
+    }
+
+    #[test]
+    fn test_hash_impl() {
+        let header = vec![7u8; 302];
+        let nonce = [1u8; 24];
+        for imp in HashImpl::ALL {
+            assert_eq!(hash_with(imp, &nonce, &header), hash(&nonce, &header));
+            assert_eq!(imp.to_string().parse::<HashImpl>().unwrap(), imp);
+        }
+        assert!("sha256".parse::<HashImpl>().is_err());
+    }
+}
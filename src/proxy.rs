@@ -0,0 +1,493 @@
+use crate::connection::{self, Peer, Writer};
+use crate::model::{Body, ClientMessage, Jobs, Message};
+use crate::network::Groups;
+use crate::{config, constant, pow, Frame};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+const CLIENT_QUEUE: usize = 64; //每个矿工待发送的帧，写满说明矿工跟不上
+
+//代理模式：下游矿工共用一个节点连接
+pub struct Proxy {
+    conf: config::Config,
+    listen: String,
+}
+
+impl Proxy {
+    pub fn new(conf: config::Config, listen: String) -> Proxy {
+        Proxy { conf, listen }
+    }
+
+    pub async fn work(&mut self) {
+        let address = format!("{}:{}", self.conf.ip, self.conf.port);
+        let listener = TcpListener::bind(&self.listen).await.unwrap();
+        info!("proxy listening on {}", self.listen);
+        let backoff = Backoff::new(
+            Duration::from_secs(constant::RECONNECT_DELAY),
+            Duration::from_secs(constant::RECONNECT_MAX_DELAY),
+        );
+        run(address, listener, Groups::from_config(&self.conf), backoff).await;
+    }
+}
+
+/// Delay before reconnecting to the node, doubled after each failed
+/// attempt up to `max`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Backoff {
+        Backoff {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    pub fn next(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+}
+
+enum Upstream {
+    Connected(Writer),
+    Message(Message),
+    Closed,
+}
+
+fn encode(msg: Message) -> Vec<u8> {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    bincode::encode_to_vec(msg, option).expect("encode_to_vec msg error")
+}
+
+//每个矿工一个写任务，慢的矿工不耽误其它矿工和节点消息
+fn spawn_writer(id: u32, mut w: Writer) -> mpsc::Sender<Frame> {
+    let (tx, mut rx) = mpsc::channel::<Frame>(CLIENT_QUEUE);
+    tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            if let Err(err) = w.write_frame(&frame).await {
+                error!("write to miner {} error {}", id, err);
+                break;
+            }
+        }
+    });
+    tx
+}
+
+//放入矿工的发送队列，队列已满或写任务已退出时返回 false，调用方断开该矿工
+fn push(id: u32, tx: &mpsc::Sender<Frame>, frame: Frame) -> bool {
+    match tx.try_send(frame) {
+        Ok(()) => true,
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            warn!("miner {} is not keeping up, drop it", id);
+            false
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
+    }
+}
+
+//连接节点并转发其消息，断开后按退避间隔重连
+fn connect(address: String, tx: mpsc::Sender<Upstream>, mut backoff: Backoff) {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    tokio::spawn(async move {
+        loop {
+            let stream = match TcpStream::connect(&address).await {
+                Ok(val) => val,
+                Err(err) => {
+                    let delay = backoff.next();
+                    error!(
+                        "connect node {} error {}, retry in {:?}",
+                        address, err, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            };
+            info!("connected to node {}", address);
+            backoff.reset();
+            let (mut r, w) = connection::pair(stream);
+            if tx.send(Upstream::Connected(w)).await.is_err() {
+                break;
+            }
+            loop {
+                match r.read_frame().await {
+                    Ok(Some(Frame::Bulk(bytes))) => {
+                        match bincode::decode_from_slice::<Message, _>(bytes.as_ref(), option) {
+                            Ok((msg, _)) => {
+                                if tx.send(Upstream::Message(msg)).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Err(err) => error!("decode upstream message error {:?}", err),
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(err) => {
+                        error!("upstream read_frame error {}", err);
+                        break;
+                    }
+                }
+            }
+            if tx.send(Upstream::Closed).await.is_err() {
+                break;
+            }
+            let delay = backoff.next();
+            error!(
+                "node {} connection closed, reconnect in {:?}",
+                address, delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+/// Relays jobs from the node at `upstream` to every miner accepted on
+/// `listener`, and their submissions back. Each miner is assigned its own
+/// nonce prefix so that they never search the same nonces. The node is
+/// reconnected with `backoff` whenever its connection is lost, and a miner
+/// that falls `CLIENT_QUEUE` frames behind is dropped.
+pub async fn run(upstream: String, listener: TcpListener, mut groups: Groups, backoff: Backoff) {
+    let (upstream_tx, mut upstream_rx) = mpsc::channel::<Upstream>(1024);
+    let (peer_tx, mut peer_rx) = mpsc::channel::<Peer>(1024);
+    connect(upstream, upstream_tx, backoff);
+    connection::serve(listener, peer_tx);
+
+    let mut upstream_w: Option<Writer> = None;
+    let mut clients: HashMap<u32, mpsc::Sender<Frame>> = HashMap::new(); //各矿工的发送队列
+    let mut rigs: HashMap<u32, String> = HashMap::new(); //发送过身份的矿工
+    let mut jobs: Option<Jobs> = None;
+    //每条链上等待结果的矿工，节点按提交顺序返回结果，已断开的矿工留空占位
+    let mut pending: HashMap<(u32, u32), VecDeque<Option<u32>>> = HashMap::new();
+    loop {
+        tokio::select! {
+            upstream = upstream_rx.recv() => match upstream {
+                Some(Upstream::Connected(w)) => upstream_w = Some(w),
+                Some(Upstream::Closed) => {
+                    //旧连接上的提交不会再有结果
+                    upstream_w = None;
+                    jobs = None;
+                    pending.clear();
+                }
+                Some(Upstream::Message(msg)) => match msg.into() {
+                    Body::Jobs(val) => {
+                        groups.update(val.len());
+                        let data = Frame::Bulk(encode(Message::jobs(val.clone())));
+                        clients.retain(|id, tx| push(*id, tx, data.clone()));
+                        jobs = Some(val);
+                    }
+                    Body::SubmitResult(ret) => {
+                        let chain = (ret.from, ret.to);
+                        let id = pending
+                            .get_mut(&chain)
+                            .and_then(|queue| queue.pop_front())
+                            .flatten();
+                        match id.and_then(|id| clients.get(&id).map(|tx| (id, tx))) {
+                            Some((id, tx)) => {
+                                info!(
+                                    "relay SubmitResult {}-{}: {} to miner {}",
+                                    ret.from, ret.to, ret.status, id
+                                );
+                                let data = Frame::Bulk(encode(Message::submit_result(ret)));
+                                if !push(id, tx, data) {
+                                    clients.remove(&id);
+                                }
+                            }
+                            None => {
+                                warn!("no miner waiting for SubmitResult {}-{}", chain.0, chain.1)
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                None => break,
+            },
+            peer = peer_rx.recv() => match peer {
+                Some(Peer::Connected(id, w)) => {
+                    let tx = spawn_writer(id, w);
+                    let mut ok = push(id, &tx, Frame::Bulk(encode(Message::nonce_prefix(id))));
+                    if let Some(val) = jobs.as_ref() {
+                        ok = ok && push(id, &tx, Frame::Bulk(encode(Message::jobs(val.clone()))));
+                    }
+                    if ok {
+                        clients.insert(id, tx);
+                    }
+                }
+                Some(Peer::Request(id, ClientMessage::Hello(hello))) => {
+                    info!(
+                        "miner {} is rig {}, addresses [{}]",
+                        id,
+                        hello.rig,
+                        hello.addresses.join(", ")
+                    );
+                    rigs.insert(id, hello.rig);
+                }
+                Some(Peer::Request(id, ClientMessage::SubmitReq(req))) => {
+                    let chain = pow::chain_index(&pow::hash(&req.nonce, &req.header), groups.get());
+                    let rig = rigs.get(&id).map_or("-", |val| val.as_str());
+                    let w = match upstream_w.as_mut() {
+                        Some(w) => w,
+                        None => {
+                            warn!(
+                                "node disconnected, drop block {}-{} of miner {} ({})",
+                                chain.0, chain.1, id, rig
+                            );
+                            continue;
+                        }
+                    };
+                    info!("miner {} ({}) submit block {}-{}", id, rig, chain.0, chain.1);
+                    let data = Frame::Bulk(encode(Message::submit_req(req)));
+                    match w.write_frame(&data).await {
+                        Ok(_) => pending.entry(chain).or_default().push_back(Some(id)),
+                        //读取端会发现连接断开并重连
+                        Err(err) => error!("upstream write_frame error {}", err),
+                    }
+                }
+                Some(Peer::Disconnected(id)) => {
+                    info!("miner {} disconnected", id);
+                    clients.remove(&id);
+                    rigs.remove(&id);
+                    for queue in pending.values_mut() {
+                        queue
+                            .iter_mut()
+                            .filter(|val| **val == Some(id))
+                            .for_each(|val| *val = None);
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, run, Backoff};
+    use crate::connection::{self, Reader, Writer};
+    use crate::model::{Body, ClientMessage, Job, Message, SubmitReq, SubmitResult};
+    use crate::network::Groups;
+    use crate::{pow, Frame};
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+    async fn read_message(r: &mut Reader) -> Message {
+        let option = bincode::config::Configuration::standard()
+            .with_big_endian()
+            .with_no_limit()
+            .with_fixed_int_encoding();
+        match r.read_frame().await.unwrap() {
+            Some(Frame::Bulk(bytes)) => {
+                bincode::decode_from_slice::<Message, _>(bytes.as_ref(), option)
+                    .unwrap()
+                    .0
+            }
+            _ => panic!("connection closed"),
+        }
+    }
+
+    async fn write_message(w: &mut Writer, msg: Message) {
+        w.write_frame(&Frame::Bulk(encode(msg))).await.unwrap();
+    }
+
+    fn backoff() -> Backoff {
+        Backoff::new(Duration::from_millis(50), Duration::from_millis(200))
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        let option = bincode::config::Configuration::standard()
+            .with_big_endian()
+            .with_no_limit()
+            .with_fixed_int_encoding();
+        let jobs = vec![Job {
+            from: 0,
+            to: 1,
+            header: vec![1; 8],
+            txs: vec![2; 4],
+            target: vec![0xff; 32],
+        }];
+
+        // in-process node: push jobs, answer one submission
+        let node = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node_address = node.local_addr().unwrap();
+        let node_jobs = jobs.clone();
+        let fake_node = tokio::spawn(async move {
+            let (stream, _) = node.accept().await.unwrap();
+            let (mut r, mut w) = connection::pair(stream);
+            write_message(&mut w, Message::jobs(node_jobs)).await;
+            let req = match r.read_frame().await.unwrap() {
+                Some(Frame::Bulk(bytes)) => {
+                    bincode::decode_from_slice::<ClientMessage, _>(bytes.as_ref(), option)
+                        .unwrap()
+                        .0
+                }
+                _ => panic!("proxy closed"),
+            };
+            let req = match req {
+                ClientMessage::SubmitReq(req) => req,
+                msg => panic!("unexpected message {:?}", msg),
+            };
+            let (from, to) = pow::chain_index(&pow::hash(&req.nonce, &req.header), 4);
+            let ret = SubmitResult {
+                from,
+                to,
+                status: true,
+            };
+            write_message(&mut w, Message::submit_result(ret)).await;
+            // keep the node connection open until the test ends
+            (req, r, w)
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_address = listener.local_addr().unwrap();
+        tokio::spawn(run(
+            node_address.to_string(),
+            listener,
+            Groups::new(Some(4)),
+            backoff(),
+        ));
+
+        let mut miners = vec![];
+        for _ in 0..2 {
+            let stream = TcpStream::connect(proxy_address).await.unwrap();
+            let (mut r, w) = connection::pair(stream);
+            let prefix = match read_message(&mut r).await.into() {
+                Body::NoncePrefix(prefix) => prefix,
+                body => panic!("unexpected message {:?}", body),
+            };
+            match read_message(&mut r).await.into() {
+                Body::Jobs(val) => assert_eq!(val, jobs),
+                body => panic!("unexpected message {:?}", body),
+            }
+            miners.push((prefix, r, w));
+        }
+        assert_ne!(miners[0].0, miners[1].0);
+
+        let req = SubmitReq {
+            nonce: vec![7; 24],
+            header: jobs[0].header.clone(),
+            txs: jobs[0].txs.clone(),
+        };
+        write_message(&mut miners[1].2, Message::submit_req(req.clone())).await;
+        match read_message(&mut miners[1].1).await.into() {
+            Body::SubmitResult(ret) => assert!(ret.status),
+            body => panic!("unexpected message {:?}", body),
+        }
+        let (received, _r, _w) = fake_node.await.unwrap();
+        assert_eq!(received, req);
+        // the result only goes back to the miner that submitted
+        let other = tokio::time::timeout(Duration::from_millis(200), miners[0].1.read_frame());
+        assert!(other.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_reconnect() {
+        let jobs = |from| {
+            vec![Job {
+                from,
+                to: 0,
+                header: vec![1; 8],
+                txs: vec![],
+                target: vec![0xff; 32],
+            }]
+        };
+        let node = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node_address = node.local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_address = listener.local_addr().unwrap();
+        tokio::spawn(run(
+            node_address.to_string(),
+            listener,
+            Groups::new(Some(4)),
+            backoff(),
+        ));
+
+        let (stream, _) = node.accept().await.unwrap();
+        let (node_r, mut node_w) = connection::pair(stream);
+        write_message(&mut node_w, Message::jobs(jobs(0))).await;
+        let stream = TcpStream::connect(proxy_address).await.unwrap();
+        let (mut r, _w) = connection::pair(stream);
+        match read_message(&mut r).await.into() {
+            Body::NoncePrefix(_) => {}
+            body => panic!("unexpected message {:?}", body),
+        }
+        match read_message(&mut r).await.into() {
+            Body::Jobs(val) => assert_eq!(val, jobs(0)),
+            body => panic!("unexpected message {:?}", body),
+        }
+
+        // the node restarts, the proxy reconnects and keeps its miners
+        drop((node_r, node_w));
+        let (stream, _) = node.accept().await.unwrap();
+        let (_node_r, mut node_w) = connection::pair(stream);
+        write_message(&mut node_w, Message::jobs(jobs(1))).await;
+        match read_message(&mut r).await.into() {
+            Body::Jobs(val) => assert_eq!(val, jobs(1)),
+            body => panic!("unexpected message {:?}", body),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_slow_miner() {
+        let node = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node_address = node.local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_address = listener.local_addr().unwrap();
+        tokio::spawn(run(
+            node_address.to_string(),
+            listener,
+            Groups::new(Some(4)),
+            backoff(),
+        ));
+        let (stream, _) = node.accept().await.unwrap();
+        let (_node_r, mut node_w) = connection::pair(stream);
+
+        // one miner never reads, the other one reads everything
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        let _stalled = socket.connect(proxy_address).await.unwrap();
+        let stream = TcpStream::connect(proxy_address).await.unwrap();
+        let (mut r, _w) = connection::pair(stream);
+        // the stalled miner's queue and socket fill up long before the last
+        // batch, after that the proxy must keep serving the other one
+        let relay = async {
+            for index in 0..600 {
+                let jobs = vec![Job {
+                    from: index % 4,
+                    to: 0,
+                    header: vec![1; 8],
+                    txs: vec![2; 16 * 1024],
+                    target: vec![0xff; 32],
+                }];
+                write_message(&mut node_w, Message::jobs(jobs.clone())).await;
+                loop {
+                    match read_message(&mut r).await.into() {
+                        Body::Jobs(val) if val == jobs => break,
+                        _ => {}
+                    }
+                }
+            }
+        };
+        assert!(tokio::time::timeout(Duration::from_secs(10), relay)
+            .await
+            .is_ok());
+    }
+}
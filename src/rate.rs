@@ -0,0 +1,123 @@
+use serde_derive::Serialize;
+use std::time;
+
+//10 秒、1 分钟、15 分钟
+const WINDOWS: [f64; 3] = [10f64, 60f64, 15f64 * 60f64];
+
+/// Hash rate as exponential moving averages over 10s, 1m and 15m windows,
+/// updated with hash counts reported at irregular intervals. Counts reported
+/// in the same instant are kept until time has advanced.
+#[derive(Debug, Clone)]
+pub struct HashRate {
+    rates: [f64; 3],
+    pending: u64, //尚未计入的计算次数
+    last: time::Instant,
+    seeded: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct HashRates {
+    #[serde(rename = "10s")]
+    pub s10: f64,
+    #[serde(rename = "1m")]
+    pub m1: f64,
+    #[serde(rename = "15m")]
+    pub m15: f64,
+}
+
+impl HashRates {
+    pub fn windows(&self) -> [(&'static str, f64); 3] {
+        [("10s", self.s10), ("1m", self.m1), ("15m", self.m15)]
+    }
+}
+
+impl Default for HashRate {
+    fn default() -> Self {
+        HashRate::new(time::Instant::now())
+    }
+}
+
+impl HashRate {
+    pub fn new(now: time::Instant) -> HashRate {
+        HashRate {
+            rates: [0f64; 3],
+            pending: 0,
+            last: now,
+            seeded: false,
+        }
+    }
+
+    pub fn record(&mut self, hashes: u64, now: time::Instant) {
+        self.pending += hashes;
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        if elapsed <= 0f64 {
+            return;
+        }
+        let sample = self.pending as f64 / elapsed;
+        if self.seeded {
+            self.rates = Self::decay(self.rates, sample, elapsed);
+        } else {
+            //第一次采样直接作为初始值
+            self.rates = [sample; 3];
+            self.seeded = true;
+        }
+        self.pending = 0;
+        self.last = now;
+    }
+
+    /// Current rates, counting the time since the last report as idle so that
+    /// a stalled worker drops off instead of keeping its last rate.
+    pub fn rates(&self, now: time::Instant) -> HashRates {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        let rates = if self.seeded && elapsed > 0f64 {
+            Self::decay(self.rates, self.pending as f64 / elapsed, elapsed)
+        } else {
+            self.rates
+        };
+        HashRates {
+            s10: rates[0],
+            m1: rates[1],
+            m15: rates[2],
+        }
+    }
+
+    fn decay(mut rates: [f64; 3], sample: f64, elapsed: f64) -> [f64; 3] {
+        for (rate, window) in rates.iter_mut().zip(WINDOWS.iter()) {
+            let alpha = 1f64 - (-elapsed / window).exp();
+            *rate += alpha * (sample - *rate);
+        }
+        rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashRate;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_hash_rate() {
+        let start = Instant::now();
+        let mut rate = HashRate::new(start);
+        // same instant: nothing to divide by yet
+        rate.record(1000, start);
+        assert_eq!(rate.rates(start).s10, 0f64);
+
+        let mut now = start + Duration::from_secs(1);
+        rate.record(1000, now);
+        assert_eq!(rate.rates(now).s10, 2000f64);
+        for _ in 0..240 {
+            now += Duration::from_millis(500);
+            rate.record(500, now);
+            rate.record(0, now);
+        }
+        let rates = rate.rates(now);
+        assert!((rates.s10 - 1000f64).abs() < 1f64);
+        assert!(rates.m1 < rates.m15 && rates.m15 < 2000f64);
+
+        // stalled: the short window drops first
+        let rates = rate.rates(now + Duration::from_secs(30));
+        assert!(rates.s10 < 100f64);
+        assert!(rates.m15 > 900f64);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::serder;
+use parking_lot::Mutex;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,  //节点发给矿工
+    Out, //矿工发给节点
+}
+
+/// One raw frame payload, `ts` in milliseconds since the recording started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub ts: u64,
+    pub dir: Direction,
+    #[serde(with = "serder::hex_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Appends every frame exchanged with the node to a JSON lines file,
+/// `--record <file>`.
+pub struct Recorder {
+    start: time::Instant,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Recorder> {
+        Ok(Recorder {
+            start: time::Instant::now(),
+            file: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn record(&self, dir: Direction, data: &[u8]) {
+        let record = Record {
+            ts: self.start.elapsed().as_millis() as u64,
+            dir,
+            data: data.to_vec(),
+        };
+        let line = serde_json::to_string(&record).expect("serialize record error");
+        let mut file = self.file.lock();
+        //逐条落盘，进程被杀时不丢记录
+        if let Err(err) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            error!("record frame error {}", err);
+        }
+    }
+}
+
+pub fn load(path: &str) -> anyhow::Result<Vec<Record>> {
+    let mut records = vec![];
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|err| anyhow::anyhow!("{}:{}: {}", path, index + 1, err))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, Direction, Recorder};
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("miner-record-{}", rand::random::<u32>()));
+        let path = path.to_str().unwrap();
+        let recorder = Recorder::create(path).unwrap();
+        recorder.record(Direction::In, &[1, 2, 3]);
+        recorder.record(Direction::Out, &[0xff]);
+        let records = load(path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].dir, Direction::In);
+        assert_eq!(records[0].data, vec![1, 2, 3]);
+        assert_eq!(records[1].dir, Direction::Out);
+        assert!(records[0].ts <= records[1].ts);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+use crate::config;
+use crate::miner::{Scheduler, Unit};
+use crate::model::{Body, ClientMessage, Job, Jobs, Message, SubmitReq, WorkUnit};
+use crate::network::Groups;
+use crate::pow;
+use crate::record::{Direction, Record};
+use crate::task::Task;
+use crate::worker::Worker;
+use bincode::Decode;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use tokio::sync::{mpsc, oneshot};
+
+/// Local verdict on a submission, against the jobs the miner had when it was
+/// sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Ok,
+    StaleJob,    //旧任务的区块头
+    UnknownJob,  //从未收到过的区块头
+    WrongChain,  //哈希不属于任务的链
+    BelowTarget, //未达到区块目标，份额或无效
+    BadNonce,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = match self {
+            Verdict::Ok => "ok",
+            Verdict::StaleJob => "stale job",
+            Verdict::UnknownJob => "unknown job",
+            Verdict::WrongChain => "wrong chain",
+            Verdict::BelowTarget => "below target",
+            Verdict::BadNonce => "bad nonce",
+        };
+        write!(f, "{}", val)
+    }
+}
+
+pub fn verdict(jobs: &Jobs, seen: &HashSet<Vec<u8>>, req: &SubmitReq, groups: u32) -> Verdict {
+    if req.nonce.len() != 24 {
+        return Verdict::BadNonce;
+    }
+    let hash = pow::hash(&req.nonce, &req.header);
+    match jobs.iter().find(|job| job.header == req.header) {
+        None if seen.contains(&req.header) => Verdict::StaleJob,
+        None => Verdict::UnknownJob,
+        Some(job) if (job.from, job.to) != pow::chain_index(&hash, groups) => Verdict::WrongChain,
+        Some(job) if !pow::check_target(&hash, &job.target) => Verdict::BelowTarget,
+        Some(_) => Verdict::Ok,
+    }
+}
+
+/// A recorded submission with the node's answer, if it came.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub ts: u64,
+    pub from: u32,
+    pub to: u32,
+    pub nonce: String,
+    pub verdict: Verdict,
+    pub accepted: Option<bool>,
+}
+
+fn decode<T: Decode>(data: &[u8]) -> Option<T> {
+    let option = bincode::config::Configuration::standard()
+        .with_big_endian()
+        .with_no_limit()
+        .with_fixed_int_encoding();
+    match bincode::decode_from_slice::<T, _>(data, option) {
+        Ok((val, _)) => Some(val),
+        Err(err) => {
+            warn!("skip undecodable frame {:?}", err);
+            None
+        }
+    }
+}
+
+//记录在收到的任务，seen 保存出现过的全部区块头
+fn track(jobs: &mut Jobs, seen: &mut HashSet<Vec<u8>>, groups: &mut Groups, body: &Body) {
+    if let Body::Jobs(val) = body {
+        seen.extend(val.iter().map(|job| job.header.clone()));
+        groups.update(val.len());
+        *jobs = val.clone();
+    }
+}
+
+/// Walks a recording, checking every submission against the jobs known when
+/// it was sent and pairing it with the node's result for that chain.
+pub fn analyze(records: &[Record], mut groups: Groups) -> Vec<Submission> {
+    let mut jobs = vec![];
+    let mut seen = HashSet::new();
+    let mut submissions: Vec<Submission> = vec![];
+    //节点按提交顺序返回同一条链的结果
+    let mut pending: HashMap<(u32, u32), VecDeque<usize>> = HashMap::new();
+    for record in records {
+        match record.dir {
+            Direction::In => match decode::<Message>(&record.data).map(Body::from) {
+                Some(Body::SubmitResult(ret)) => {
+                    let index = pending
+                        .get_mut(&(ret.from, ret.to))
+                        .and_then(|queue| queue.pop_front());
+                    if let Some(index) = index {
+                        submissions[index].accepted = Some(ret.status);
+                    }
+                }
+                Some(body) => track(&mut jobs, &mut seen, &mut groups, &body),
+                None => {}
+            },
+            Direction::Out => {
+                if let Some(ClientMessage::SubmitReq(req)) = decode::<ClientMessage>(&record.data) {
+                    let hash = pow::hash(&req.nonce, &req.header);
+                    let (from, to) = pow::chain_index(&hash, groups.get());
+                    pending
+                        .entry((from, to))
+                        .or_default()
+                        .push_back(submissions.len());
+                    submissions.push(Submission {
+                        ts: record.ts,
+                        from,
+                        to,
+                        nonce: hex::encode(&req.nonce),
+                        verdict: verdict(&jobs, &seen, &req, groups.get()),
+                        accepted: None,
+                    });
+                }
+            }
+        }
+    }
+    submissions
+}
+
+//回放时的 nonce 前缀和线程段，固定下来每次尝试相同的 nonce
+const NONCE_PREFIX: u32 = 0;
+const NONCE_SEED: u32 = 0;
+//每个任务最多计算 MINING_STEPS × 100000 次，按次数而不是时间结束任务
+const MINING_STEPS: u64 = 2;
+
+/// The config a replay runs with: no recording, api, control socket,
+/// dashboard or notifications of the live miner.
+pub fn replay_config(conf: &config::Config) -> config::Config {
+    config::Config {
+        record: None,
+        api: None,
+        api_control: false,
+        control_socket: None,
+        tui: false,
+        notify: Default::default(),
+        ..conf.clone()
+    }
+}
+
+//一定立即找到的任务，排在一批任务之后，它返回时前面的任务都已算完
+fn barrier() -> Task {
+    Task::new()
+        .with_job(Job {
+            target: vec![0xff; 32],
+            ..Default::default()
+        })
+        .with_groups(1)
+}
+
+/// Feeds the node side of a recording to a `Scheduler` with one worker, in
+/// recorded order and without the recorded timing. The nonce prefix and
+/// seed are fixed and each batch of jobs is mined to the end before the next
+/// frame, so a recording always gives the same submissions. Returns the
+/// verdicts on them.
+pub async fn feed(records: &[Record], conf: &config::Config, mut groups: Groups) -> Vec<Verdict> {
+    let conf = replay_config(conf);
+    let (unit_tx, unit_rx) = mpsc::channel::<Unit>(16);
+    let (task_tx, mut task_rx) = mpsc::channel::<Task>(1024);
+    let (work_tx, work_rx) = crossbeam::channel::unbounded::<WorkUnit>();
+    let mut worker = Worker::new(task_tx, work_rx.clone())
+        .with_nonce_seed(NONCE_SEED)
+        .with_mining_steps(MINING_STEPS)
+        .with_hash_impl(conf.hash_impl);
+    let notifier = worker.notifier();
+    let thread = std::thread::spawn(move || worker.work());
+    let mut scheduler = Scheduler::new()
+        .with_share_target(conf.share_target.clone())
+        .with_nonce_prefix(NONCE_PREFIX)
+        .with_groups(groups)
+        .with_rx(unit_rx)
+        .with_config(conf)
+        .with_receiver(work_rx)
+        .with_sender(work_tx.clone());
+    let scheduler = tokio::spawn(async move { scheduler.work().await });
+
+    let mut jobs = vec![];
+    let mut seen = HashSet::new();
+    let mut verdicts = vec![];
+    for record in records.iter().filter(|record| record.dir == Direction::In) {
+        let msg = match decode::<Message>(&record.data) {
+            Some(msg) => msg,
+            None => continue,
+        };
+        let body = Body::from(msg.clone());
+        track(&mut jobs, &mut seen, &mut groups, &body);
+        let (done_tx, done_rx) = oneshot::channel();
+        if unit_tx.send(Unit::MSG(msg)).await.is_err()
+            || unit_tx.send(Unit::Flush(done_tx)).await.is_err()
+            || done_rx.await.is_err()
+        {
+            error!("replay scheduler stopped");
+            break;
+        }
+        if !matches!(body, Body::Jobs(_)) {
+            continue;
+        }
+        let barrier = barrier();
+        let id = barrier.task_id();
+        work_tx.send(WorkUnit::TaskReq(barrier)).unwrap();
+        while let Some(task) = task_rx.recv().await {
+            if task.task_id() == id {
+                break;
+            }
+            //找到的区块和份额
+            if task.status() == 0 || task.status() == 4 {
+                let req = SubmitReq {
+                    nonce: task.nonce().to_vec(),
+                    header: task.job().header.clone(),
+                    txs: task.job().txs.clone(),
+                };
+                verdicts.push(verdict(&jobs, &seen, &req, groups.get()));
+            }
+        }
+    }
+    scheduler.abort();
+    notifier.stop();
+    drop(work_tx);
+    let _ = thread.join();
+    verdicts
+}
+
+/// `replay <file>`: reports on the recorded submissions, then feeds the
+/// recording to a local scheduler and compares what it submits.
+pub struct Replay {
+    conf: config::Config,
+    records: Vec<Record>,
+}
+
+impl Replay {
+    pub fn new(conf: config::Config, records: Vec<Record>) -> Replay {
+        Replay { conf, records }
+    }
+
+    pub async fn work(&mut self) {
+        let groups = Groups::from_config(&self.conf);
+        let recorded = analyze(&self.records, groups);
+        println!("recorded submissions: {}", recorded.len());
+        for (index, val) in recorded.iter().enumerate() {
+            let node = match val.accepted {
+                Some(true) => "accepted",
+                Some(false) => "rejected",
+                None => "no result",
+            };
+            println!(
+                "#{} {}ms chain {}-{} nonce {}: {}, node {}",
+                index, val.ts, val.from, val.to, val.nonce, val.verdict, node
+            );
+        }
+
+        let replayed = feed(&self.records, &self.conf, groups).await;
+        let ok = replayed.iter().filter(|val| **val == Verdict::Ok).count();
+        println!(
+            "replayed submissions: {} ({} ok), recorded: {} ({} ok)",
+            replayed.len(),
+            ok,
+            recorded.len(),
+            recorded
+                .iter()
+                .filter(|val| val.verdict == Verdict::Ok)
+                .count()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, feed, Verdict};
+    use crate::config::Config;
+    use crate::model::{Job, Message, SubmitReq, SubmitResult};
+    use crate::network::{self, Groups};
+    use crate::pow;
+    use crate::record::{Direction, Record};
+
+    fn encode(msg: Message) -> Vec<u8> {
+        let option = bincode::config::Configuration::standard()
+            .with_big_endian()
+            .with_no_limit()
+            .with_fixed_int_encoding();
+        bincode::encode_to_vec(msg, option).unwrap()
+    }
+
+    fn job(header: u8) -> Job {
+        Job {
+            from: 0,
+            to: 0,
+            header: vec![header; network::header_size(4)],
+            txs: vec![],
+            target: vec![0x0f; 32],
+        }
+    }
+
+    //找一个落在任务链上的 nonce
+    fn solve(job: &Job) -> SubmitReq {
+        let mut nonce = vec![0u8; 24];
+        for i in 0u64.. {
+            nonce[16..].copy_from_slice(&i.to_be_bytes());
+            let hash = pow::hash(&nonce, &job.header);
+            if pow::chain_index(&hash, 4) == (job.from, job.to)
+                && pow::check_target(&hash, &job.target)
+            {
+                break;
+            }
+        }
+        SubmitReq {
+            nonce,
+            header: job.header.clone(),
+            txs: job.txs.clone(),
+        }
+    }
+
+    fn records() -> Vec<Record> {
+        let old = job(1);
+        let new = job(2);
+        let record = |ts, dir, data| Record { ts, dir, data };
+        vec![
+            record(0, Direction::In, encode(Message::jobs(vec![old.clone()]))),
+            record(10, Direction::In, encode(Message::jobs(vec![new.clone()]))),
+            record(20, Direction::Out, encode(Message::submit_req(solve(&new)))),
+            record(30, Direction::Out, encode(Message::submit_req(solve(&old)))),
+            record(
+                40,
+                Direction::In,
+                encode(Message::submit_result(SubmitResult {
+                    from: 0,
+                    to: 0,
+                    status: true,
+                })),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_analyze() {
+        let submissions = analyze(&records(), Groups::new(Some(4)));
+        assert_eq!(submissions.len(), 2);
+        assert_eq!(submissions[0].verdict, Verdict::Ok);
+        assert_eq!(submissions[0].accepted, Some(true));
+        assert_eq!(submissions[1].verdict, Verdict::StaleJob);
+        assert_eq!(submissions[1].accepted, None);
+    }
+
+    #[tokio::test]
+    async fn test_feed() {
+        let conf = Config {
+            worker_num: 1,
+            ..Default::default()
+        };
+        let first = feed(&records(), &conf, Groups::new(Some(4))).await;
+        assert!(!first.is_empty());
+        assert!(first.iter().all(|val| *val == Verdict::Ok));
+        // the same nonces are tried on every run
+        let second = feed(&records(), &conf, Groups::new(Some(4))).await;
+        assert_eq!(first, second);
+    }
+}
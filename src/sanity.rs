@@ -0,0 +1,149 @@
+//! Sanity checks on the jobs a node sends: a broken or misconfigured node
+//! must not keep the workers hashing on jobs that can never be accepted.
+
+use crate::model::Job;
+use crate::network;
+use crate::pow;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Why a job was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    Chain,               //from、to 超出分组数
+    Duplicate,           //同一条链出现多次
+    TargetLength(usize), //目标为空或超过 32 字节
+    ZeroTarget,          //没有哈希能达到
+    EasyTarget(u32),     //前导零位数不足
+    HeaderLength(usize), //区块头长度不对
+}
+
+impl Problem {
+    /// Label of the problem in stats and metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Problem::Chain => "chain",
+            Problem::Duplicate => "duplicate",
+            Problem::TargetLength(_) => "target_length",
+            Problem::ZeroTarget => "zero_target",
+            Problem::EasyTarget(_) => "easy_target",
+            Problem::HeaderLength(_) => "header_length",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Chain => write!(f, "chain outside the groups"),
+            Problem::Duplicate => write!(f, "duplicate chain"),
+            Problem::TargetLength(len) => write!(f, "target of {} bytes", len),
+            Problem::ZeroTarget => write!(f, "zero target"),
+            Problem::EasyTarget(bits) => {
+                write!(f, "absurdly easy target, {} leading zero bits", bits)
+            }
+            Problem::HeaderLength(len) => write!(f, "header of {} bytes", len),
+        }
+    }
+}
+
+/// What jobs of a network of `groups` groups look like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checker {
+    pub groups: u32,
+    pub min_zero_bits: u32, //目标至少的前导零位数
+}
+
+impl Checker {
+    /// The first problem of each rejected job, by index in `jobs`.
+    pub fn check(&self, jobs: &[Job]) -> Vec<(usize, Problem)> {
+        let mut chains = HashSet::new();
+        jobs.iter()
+            .enumerate()
+            .filter_map(|(index, job)| {
+                let problem = self.problem(job);
+                //有问题的任务不占用链，后面同链的正常任务仍然可用
+                if problem.is_none() && !chains.insert((job.from, job.to)) {
+                    return Some((index, Problem::Duplicate));
+                }
+                problem.map(|val| (index, val))
+            })
+            .collect()
+    }
+
+    fn problem(&self, job: &Job) -> Option<Problem> {
+        if job.from >= self.groups || job.to >= self.groups {
+            return Some(Problem::Chain);
+        }
+        if job.target.is_empty() || job.target.len() > 32 {
+            return Some(Problem::TargetLength(job.target.len()));
+        }
+        if job.target.iter().all(|byte| *byte == 0) {
+            return Some(Problem::ZeroTarget);
+        }
+        let bits = pow::zero_bits(&job.target);
+        if bits < self.min_zero_bits {
+            return Some(Problem::EasyTarget(bits));
+        }
+        if job.header.len() != network::header_size(self.groups) {
+            return Some(Problem::HeaderLength(job.header.len()));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checker, Problem};
+    use crate::model::Job;
+    use crate::network;
+
+    fn job(from: u32, to: u32) -> Job {
+        Job {
+            from,
+            to,
+            header: vec![1; network::header_size(4)],
+            txs: vec![],
+            target: vec![0x0f; 28],
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let checker = Checker {
+            groups: 4,
+            min_zero_bits: 1,
+        };
+        let jobs: Vec<Job> = (0..16).map(|index| job(index / 4, index % 4)).collect();
+        assert!(checker.check(&jobs).is_empty());
+
+        let mut bad = jobs.clone();
+        bad[0].from = 4;
+        bad[1].target = vec![0; 33];
+        bad[2].target = vec![0; 32];
+        bad[3].target = vec![0xff; 32];
+        bad[4].header.pop();
+        bad.push(job(3, 3));
+        assert_eq!(
+            checker.check(&bad),
+            vec![
+                (0, Problem::Chain),
+                (1, Problem::TargetLength(33)),
+                (2, Problem::ZeroTarget),
+                (3, Problem::EasyTarget(0)),
+                (4, Problem::HeaderLength(301)),
+                (16, Problem::Duplicate),
+            ]
+        );
+
+        // mainnet targets have at least 37 leading zero bits
+        let checker = Checker {
+            groups: 4,
+            min_zero_bits: 37,
+        };
+        assert_eq!(
+            checker.check(&jobs[..1]),
+            vec![(0, Problem::EasyTarget(36))]
+        );
+    }
+}
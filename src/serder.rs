@@ -1 +1,35 @@
+//! serde helpers for the config file and recordings.
 
+/// `Option<Vec<u8>>` as an optional hex string.
+pub mod hex_option {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(val: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match val {
+            Some(val) => s.serialize_some(&hex::encode(val)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        let val: Option<String> = Option::deserialize(d)?;
+        val.map(|val| hex::decode(val).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// `Vec<u8>` as a hex string.
+pub mod hex_bytes {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(val: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(val))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let val = String::deserialize(d)?;
+        hex::decode(val).map_err(D::Error::custom)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::constant;
 use crate::model;
 use crate::model::Job;
 use std::default::Default;
@@ -7,13 +8,17 @@ use std::time;
 pub struct Task {
     task_id: u64,
     worker_id: String,
-    job: model::Job,           //当前计算的任务
-    hash_count: u64,           //当前计算次数
-    hash_rate: u64,            //当前任务的算力
-    start_time: time::Instant, //单次任务开始计算时间
-    end_time: time::Instant,   //单次任务结束计算时间
-    nonce: [u8; 24],           //nonce,最终状态的nonce值
-    status: usize,             //0:成功，1：limit timeout, 2:被动放弃, 3: 失败
+    job: model::Job,                   //当前计算的任务
+    hash_count: u64,                   //当前计算次数
+    hash_rate: u64,                    //当前任务的算力
+    received_time: time::Instant,      //任务到达时间
+    start_time: time::Instant,         //单次任务开始计算时间
+    end_time: time::Instant,           //单次任务结束计算时间
+    nonce: [u8; 24],                   //nonce,最终状态的nonce值
+    status: usize,                     //0:成功，1：limit timeout, 2:被动放弃, 3: 失败, 4: 份额
+    share_target: Option<model::Blob>, //矿池份额目标
+    nonce_prefix: u32,                 //nonce 前缀，由代理分配或随机生成
+    groups: u32,                       //网络分组数，决定哈希所属的链
 }
 
 impl Default for Task {
@@ -24,11 +29,15 @@ impl Default for Task {
             job: Default::default(),
             hash_count: 0,
             hash_rate: 0,
+            received_time: time::Instant::now(),
             start_time: time::Instant::now(),
             end_time: time::Instant::now(),
             // ..Default::default()
             nonce: Default::default(),
             status: 0,
+            share_target: None,
+            nonce_prefix: 0,
+            groups: constant::GROUP_NUMS,
         }
     }
 }
@@ -36,7 +45,8 @@ impl Default for Task {
 impl Task {
     pub fn new() -> Task {
         let mut t = Task::default();
-        t.start_time = time::Instant::now();
+        t.received_time = time::Instant::now();
+        t.start_time = t.received_time;
         t.task_id = rand::random();
         t
     }
@@ -45,8 +55,12 @@ impl Task {
         self.task_id
     }
 
-    pub fn job(&mut self) -> &mut Job {
-        &mut self.job
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+
+    pub fn job(&self) -> &Job {
+        &self.job
     }
 
     pub fn nonce(&self) -> &[u8] {
@@ -61,11 +75,43 @@ impl Task {
         self.status
     }
 
+    //排队等待计算的时间
+    pub fn wait_time(&self) -> time::Duration {
+        self.start_time - self.received_time
+    }
+
+    pub fn share_target(&self) -> Option<&model::Blob> {
+        self.share_target.as_ref()
+    }
+
+    pub fn nonce_prefix(&self) -> u32 {
+        self.nonce_prefix
+    }
+
+    pub fn groups(&self) -> u32 {
+        self.groups
+    }
+
     pub fn with_job(mut self, t: Job) -> Self {
         self.job = t;
         self
     }
 
+    pub fn with_share_target(mut self, t: Option<model::Blob>) -> Self {
+        self.share_target = t;
+        self
+    }
+
+    pub fn with_nonce_prefix(mut self, t: u32) -> Self {
+        self.nonce_prefix = t;
+        self
+    }
+
+    pub fn with_groups(mut self, t: u32) -> Self {
+        self.groups = t;
+        self
+    }
+
     pub fn with_worker_id(mut self, t: String) -> Self {
         self.worker_id = t;
         self
@@ -76,6 +122,11 @@ impl Task {
         self
     }
 
+    //矿工开始计算
+    pub fn start(self) -> Self {
+        self.with_start_time(time::Instant::now())
+    }
+
     fn with_end_time(mut self, t: time::Instant) -> Self {
         self.end_time = t;
         self
@@ -95,6 +146,15 @@ impl Task {
         self.hash_count
     }
 
+    pub fn hash_rate(&self) -> u64 {
+        self.hash_rate
+    }
+
+    //开始计算到结束的时长
+    pub fn elapsed(&self) -> time::Duration {
+        self.end_time - self.start_time
+    }
+
     pub fn with_status(mut self, t: usize) -> Self {
         self.status = t;
         self
@@ -102,8 +162,13 @@ impl Task {
 
     pub fn build(mut self) -> Self {
         self.end_time = time::Instant::now();
-        let consume_time = (self.end_time - self.start_time).as_secs();
-        self.hash_rate = self.hash_count / consume_time;
+        let consume_time = (self.end_time - self.start_time).as_secs_f64();
+        //耗时为零时算力记 0，避免除零
+        self.hash_rate = if consume_time > 0f64 {
+            (self.hash_count as f64 / consume_time) as u64
+        } else {
+            0
+        };
         self
     }
 }
@@ -114,4 +179,14 @@ mod tests {
     use chrono::Timelike;
     #[test]
     fn test_task() {}
+
+    #[test]
+    fn test_hash_rate() {
+        let task = super::Task::new()
+            .with_start_time(std::time::Instant::now() - std::time::Duration::from_millis(100))
+            .with_hash_count(1000)
+            .build();
+        // 1000 hashes in a little over 0.1 second, not in a whole second
+        assert!(task.hash_rate() > 5000 && task.hash_rate() <= 10000);
+    }
 }
@@ -0,0 +1,195 @@
+//! Resource limits: a duty cycle applied by workers at their checkpoints and
+//! an idle-only mode suspending them while other processes load the system.
+
+use crate::config::ThrottleConfig;
+use crate::worker::{Notifier, SharedNotifiers};
+use parking_lot::RwLock;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+const LOADAVG: &str = "/proc/loadavg";
+//检查系统负载的间隔
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Updated when workers are added or removed, the cap is per worker.
+pub type SharedThrottle = Arc<RwLock<Throttle>>;
+
+/// Per worker duty cycle: a share of one core and/or a hash rate cap.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Throttle {
+    cpu: Option<f64>,       //占用单核的百分比
+    hash_rate: Option<f64>, //单个工作线程的算力上限
+}
+
+impl Throttle {
+    /// The total hash rate cap is shared evenly between `workers`.
+    pub fn new(conf: &ThrottleConfig, workers: usize) -> Throttle {
+        Throttle {
+            cpu: conf.cpu.filter(|val| *val > 0f64 && *val < 100f64),
+            hash_rate: conf
+                .hash_rate
+                .filter(|val| *val > 0f64)
+                .map(|val| val / workers.max(1) as f64),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cpu.is_some() || self.hash_rate.is_some()
+    }
+
+    /// Sleep owed after computing `hashes` in `busy`, the longest of what
+    /// each limit asks for.
+    pub fn delay(&self, hashes: u64, busy: Duration) -> Duration {
+        let busy = busy.as_secs_f64();
+        let by_cpu = self.cpu.map_or(0f64, |val| busy * (100f64 - val) / val);
+        let by_rate = self
+            .hash_rate
+            .map_or(0f64, |val| hashes as f64 / val - busy);
+        Duration::from_secs_f64(by_cpu.max(by_rate).max(0f64))
+    }
+}
+
+/// 1 minute load average.
+pub fn load_average() -> Option<f64> {
+    let content = fs::read_to_string(LOADAVG).ok()?;
+    content.split_whitespace().next()?.parse::<f64>().ok()
+}
+
+//去掉本矿工正在计算的线程后的负载
+pub fn other_load(load: f64, notifiers: &[Arc<Notifier>]) -> f64 {
+    let mining = notifiers
+        .iter()
+        .filter(|val| val.is_busy() && !val.is_paused() && !val.is_suspended())
+        .count();
+    (load - mining as f64).max(0f64)
+}
+
+/// Suspends every worker while the load of other processes is above
+/// `threshold` and resumes them once it drops below. The 1 minute average
+/// lags, so both take up to a minute to kick in.
+pub async fn idle_watch(threshold: f64, notifiers: SharedNotifiers) {
+    let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    let mut suspended = false;
+    loop {
+        interval.tick().await;
+        let load = match load_average() {
+            Some(load) => load,
+            None => {
+                warn!("read {} error, idle-only mode disabled", LOADAVG);
+                return;
+            }
+        };
+        let notifiers = notifiers.read().clone();
+        let other = other_load(load, &notifiers);
+        if !suspended && other > threshold {
+            info!(
+                "system load {:.2} above {:.2}, suspend mining",
+                other, threshold
+            );
+            notifiers.iter().for_each(|val| val.suspend());
+            suspended = true;
+        } else if suspended && other <= threshold {
+            info!(
+                "system load {:.2} below {:.2}, resume mining",
+                other, threshold
+            );
+            notifiers.iter().for_each(|val| val.unsuspend());
+            suspended = false;
+        } else if suspended {
+            //期间新增的工作线程
+            notifiers.iter().for_each(|val| val.suspend());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{other_load, Throttle};
+    use crate::config::ThrottleConfig;
+    use crate::model::{Job, WorkUnit};
+    use crate::task::Task;
+    use crate::worker::{Notifier, Worker};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_delay() {
+        let busy = Duration::from_millis(100);
+        assert!(!Throttle::new(&ThrottleConfig::default(), 4).is_enabled());
+        assert_eq!(Throttle::default().delay(100000, busy), Duration::ZERO);
+
+        let conf = ThrottleConfig {
+            cpu: Some(25f64),
+            ..Default::default()
+        };
+        // 25% of a core: 100ms of work, 300ms of sleep
+        assert_eq!(
+            Throttle::new(&conf, 4).delay(100000, busy),
+            Duration::from_millis(300)
+        );
+
+        let conf = ThrottleConfig {
+            hash_rate: Some(400000f64),
+            ..Default::default()
+        };
+        // 100k hashes/s per worker, so 100k hashes take one second
+        let throttle = Throttle::new(&conf, 4);
+        assert_eq!(throttle.delay(100000, busy), Duration::from_millis(900));
+        assert_eq!(
+            throttle.delay(100000, Duration::from_secs(2)),
+            Duration::ZERO
+        );
+
+        let conf = ThrottleConfig {
+            cpu: Some(50f64),
+            hash_rate: Some(400000f64),
+            ..Default::default()
+        };
+        assert_eq!(
+            Throttle::new(&conf, 4).delay(100000, busy),
+            Duration::from_millis(900)
+        );
+    }
+
+    #[test]
+    fn test_other_load() {
+        let notifiers = vec![Arc::new(Notifier::new()), Arc::new(Notifier::new())];
+        // idle workers do not count
+        assert_eq!(other_load(1.5, &notifiers), 1.5);
+        assert_eq!(other_load(0.5, &notifiers), 0.5);
+
+        // workers mining an unreachable target are the miner's own load
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let mut notifiers = vec![];
+        let mut handles = vec![];
+        for _ in 0..2 {
+            let mut worker = Worker::new(tx.clone(), receiver.clone()).with_mining_steps(1000);
+            notifiers.push(Arc::new(worker.notifier()));
+            handles.push(thread::spawn(move || worker.work()));
+            let task = Task::new().with_job(Job {
+                header: vec![1; 302],
+                target: vec![0; 32],
+                ..Default::default()
+            });
+            sender.send(WorkUnit::TaskReq(task)).unwrap();
+        }
+        while !notifiers.iter().all(|val| val.is_busy()) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(other_load(3.5, &notifiers), 1.5);
+        assert_eq!(other_load(1.5, &notifiers), 0f64);
+        // a paused worker no longer loads the machine
+        notifiers[0].pause();
+        assert_eq!(other_load(3.5, &notifiers), 2.5);
+
+        notifiers.iter().for_each(|val| val.notify());
+        for _ in 0..2 {
+            rx.blocking_recv().expect("worker exited");
+        }
+        drop(sender);
+        handles.into_iter().for_each(|val| val.join().unwrap());
+    }
+}
@@ -1,26 +1,40 @@
 use crate::constant;
 use crate::counter::Counter;
+use crate::event::Event;
 use crate::model;
 use crate::model::{Job, WorkUnit};
+use crate::pow;
 use crate::task::Task;
+use crate::throttle::SharedThrottle;
 use blake3;
 use blake3::Hash;
 use crossbeam::channel;
+use parking_lot::RwLock;
 use std::sync::atomic;
 use std::sync::Arc;
+use std::{thread, time};
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 
 //单个线程的算力
 pub struct Worker {
-    worker_id: String,                //矿工号
-    counter: Counter,                 //统计器
-    miner_hash_limit: u64,            //单次任务挖矿最大限制，主动放弃当前任务。
-    current_nonce: [u8; 24],          //当前nonce
-    increase_nonce: u128,             //递增值
-    is_free: Arc<atomic::AtomicBool>, //被动通知需要下拉最新的任务。true: 被通知，false: 不需要。
-    sender: mpsc::Sender<Task>,       //???
+    worker_id: String,                   //矿工号
+    counter: Counter,                    //统计器
+    miner_hash_limit: u64,               //单次任务挖矿最大限制，主动放弃当前任务。
+    current_nonce: [u8; 24],             //当前nonce
+    increase_nonce: u128,                //递增值
+    nonce_seed: u32,                     //线程随机段，nonce = 前缀(4) + 线程段(4) + 递增值(16)
+    is_free: Arc<atomic::AtomicBool>,    //被动通知需要下拉最新的任务。true: 被通知，false: 不需要。
+    hashes: Arc<atomic::AtomicU64>,      //检查点上报、尚未被调度器取走的计算次数
+    task_hashes: Arc<atomic::AtomicU64>, //当前任务已计算次数，空闲时为 0
+    busy: Arc<atomic::AtomicBool>,       //是否正在计算
+    paused: Arc<atomic::AtomicBool>,     //暂停时停在检查点等待恢复
+    suspended: Arc<atomic::AtomicBool>,  //系统繁忙时自动暂停，与手动暂停分开
+    stopped: Arc<atomic::AtomicBool>,    //运行时移除，结束当前任务后线程退出
+    hash_impl: pow::HashImpl,            //哈希实现
+    throttle: SharedThrottle,            //检查点处按占空比休眠，全部工作线程共享
+    sender: mpsc::Sender<Task>,          //???
     rx: channel::Receiver<model::WorkUnit>,
 }
 
@@ -28,16 +42,91 @@ pub struct Worker {
 pub struct Notifier {
     work_id: String,
     is_free: Arc<atomic::AtomicBool>,
+    hashes: Arc<atomic::AtomicU64>,
+    task_hashes: Arc<atomic::AtomicU64>,
+    busy: Arc<atomic::AtomicBool>,
+    paused: Arc<atomic::AtomicBool>,
+    suspended: Arc<atomic::AtomicBool>,
+    stopped: Arc<atomic::AtomicBool>,
 }
 
+/// The live worker set, changed at runtime by the `Scheduler`.
+pub type SharedNotifiers = Arc<RwLock<Vec<Arc<Notifier>>>>;
+
+//空闲线程检查退出标记的间隔
+const STOP_CHECK: time::Duration = time::Duration::from_millis(100);
+
 impl Notifier {
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn notify(&mut self) {
+    pub fn notify(&self) {
         self.is_free.store(true, atomic::Ordering::Relaxed);
     }
+
+    pub fn worker_id(&self) -> &str {
+        &self.work_id
+    }
+
+    //取走上次以来的计算次数
+    pub fn take_hashes(&self) -> u64 {
+        self.hashes.swap(0, atomic::Ordering::Relaxed)
+    }
+
+    pub fn task_hashes(&self) -> u64 {
+        self.task_hashes.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.busy.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn suspend(&self) {
+        self.suspended.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn unsuspend(&self) {
+        self.suspended.store(false, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Ends the worker thread once its current task is interrupted.
+    pub fn stop(&self) {
+        self.stopped.store(true, atomic::Ordering::Relaxed);
+        self.notify();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn state(&self) -> &'static str {
+        if self.is_paused() {
+            "paused"
+        } else if self.is_suspended() {
+            "suspended"
+        } else if self.is_busy() {
+            "mining"
+        } else {
+            "idle"
+        }
+    }
 }
 
 impl Worker {
@@ -46,84 +135,163 @@ impl Worker {
             worker_id: Uuid::new_v4().to_string(),
             miner_hash_limit: constant::MINING_STEPS,
             is_free: Arc::new(Default::default()),
+            hashes: Arc::new(Default::default()),
+            task_hashes: Arc::new(Default::default()),
+            busy: Arc::new(Default::default()),
+            paused: Arc::new(Default::default()),
+            suspended: Arc::new(Default::default()),
+            stopped: Arc::new(Default::default()),
+            hash_impl: Default::default(),
+            throttle: Default::default(),
             // current_task: Default::default(),
             current_nonce: Default::default(),
             counter: Counter::new(),
             increase_nonce: 0,
+            nonce_seed: rand::random(),
             sender,
             rx,
         }
     }
 
+    /// Replaces the random id, must come before `notifier`.
+    pub fn with_worker_id(mut self, worker_id: String) -> Self {
+        self.worker_id = worker_id;
+        self
+    }
+
+    /// Replaces the random thread segment of the nonces, for replays that
+    /// must try the same nonces every run.
+    pub fn with_nonce_seed(mut self, seed: u32) -> Self {
+        self.nonce_seed = seed;
+        self
+    }
+
+    pub fn with_mining_steps(mut self, steps: u64) -> Self {
+        self.miner_hash_limit = steps;
+        self
+    }
+
+    pub fn with_throttle(mut self, throttle: SharedThrottle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_hash_impl(mut self, hash_impl: pow::HashImpl) -> Self {
+        self.hash_impl = hash_impl;
+        self
+    }
+
     pub fn work(&mut self) {
-        let mut ret = Ok(WorkUnit::default());
-        {
-            ret = self.rx.recv();
-        }
-        match ret {
-            Ok(val) => match val {
-                WorkUnit::TaskReq(mut task) => {
-                    info!("worker id: {}, task id: {}", self.worker_id, task.task_id());
-                    let job = task.job();
-
-                    let (status, count) = self.mining(job);
-                    info!("worker id: {}, from: {}, to: {}, target: {:?}, header: {:?}, current_nonce: {:?}, status: {}",
-                           self.worker_id,job.from,job.to,job.target,job.header,self.current_nonce,status);
-                    let task = task
-                        .with_worker_id(self.worker_id.clone())
-                        .with_nonce(self.current_nonce.clone())
-                        .with_status(status)
-                        .with_hash_count(count)
-                        .build();
-
-                    self.counter.add(task.clone());
-                    self.sender.blocking_send(task).unwrap();
+        while !self.stopped.load(atomic::Ordering::Relaxed) {
+            match self.rx.recv_timeout(STOP_CHECK) {
+                Ok(val) => match val {
+                    WorkUnit::TaskReq(task) => {
+                        let task = task.start();
+                        Event::TaskStarted {
+                            worker: self.worker_id.clone(),
+                            task_id: task.task_id(),
+                            from: task.job().from,
+                            to: task.job().to,
+                        }
+                        .emit();
+                        let (status, count) = self.mining(&task);
+                        if status == 0 {
+                            self.found(&task, false);
+                        } else {
+                            debug!(
+                                "worker id: {}, task id: {}, nonce: {}, status: {}",
+                                self.worker_id,
+                                task.task_id(),
+                                hex::encode(self.current_nonce),
+                                status
+                            );
+                        }
+                        let task = task
+                            .with_worker_id(self.worker_id.clone())
+                            .with_nonce(self.current_nonce.clone())
+                            .with_status(status)
+                            .with_hash_count(count)
+                            .build();
+
+                        self.counter.add(task.clone());
+                        self.sender.blocking_send(task).unwrap();
+                    }
+                    WorkUnit::TaskRes(job_id, ret) => {
+                        //TODO
+                    }
+                },
+                Err(channel::RecvTimeoutError::Timeout) => {}
+                Err(err) => {
+                    //任务队列已关闭，线程退出
+                    error!("worker: {} recv data error: {}", self.worker_id, err);
+                    break;
                 }
-                WorkUnit::TaskRes(job_id, ret) => {
-                    //TODO
-                }
-            },
-            Err(err) => {
-                error!("worker: {} recv data error: {}", self.worker_id, err);
             }
         }
     }
 
     fn increase_nonce(&mut self) {
         self.increase_nonce += 1;
-        self.current_nonce[..16].copy_from_slice(&u128::to_be_bytes(self.increase_nonce));
+        self.current_nonce[8..].copy_from_slice(&u128::to_be_bytes(self.increase_nonce));
+    }
+
+    //前缀按连接分配，不同矿工的 nonce 空间互不重叠；线程段随机，同一矿工的线程只是极少重叠
+    fn prefix_nonce(&mut self, prefix: u32) {
+        self.current_nonce[..4].copy_from_slice(&prefix.to_be_bytes());
+        self.current_nonce[4..8].copy_from_slice(&self.nonce_seed.to_be_bytes());
     }
 
     fn reset(&mut self) {
         self.reset_nonce()
     }
 
-    fn mining(&mut self, job: &mut Job) -> (usize, u64) {
+    fn mining(&mut self, task: &Task) -> (usize, u64) {
+        let job = task.job();
         let mut step_count = 0;
         let mut total_count = 0;
+        let mut checkpoint = time::Instant::now();
+        self.prefix_nonce(task.nonce_prefix());
         self.is_free.store(false, atomic::Ordering::Relaxed);
-        loop {
+        self.busy.store(true, atomic::Ordering::Relaxed);
+        let ret = loop {
             self.increase_nonce();
             let double_hash = self.double2(job);
-            step_count += 2;
-            total_count += 2;
-            let is = Worker::check_hash(
-                double_hash,
-                job.target.clone(),
-                job.from.clone(),
-                job.to.clone(),
-            );
+            step_count += pow::HASHES_PER_NONCE;
+            total_count += pow::HASHES_PER_NONCE;
+            let is_share = match task.share_target() {
+                Some(target) => {
+                    Worker::check_hash(&double_hash, target, job.from, job.to, task.groups())
+                }
+                None => false,
+            };
+            let is = Worker::check_hash(&double_hash, &job.target, job.from, job.to, task.groups());
             if is {
                 break (0, total_count);
             }
+            if is_share {
+                self.submit_share(task);
+            }
             if step_count > self.miner_hash_limit {
                 // if !self.rx.is_empty() {
                 //     break (1, total_count);
                 // }
+                self.report(step_count);
+                self.task_hashes
+                    .store(total_count, atomic::Ordering::Relaxed);
+                let delay = self.throttle.read().delay(step_count, checkpoint.elapsed());
+                self.rest(delay);
+                step_count = 0;
+                //暂停时停在检查点，新任务到达仍然退出
+                while (self.paused.load(atomic::Ordering::Relaxed)
+                    || self.suspended.load(atomic::Ordering::Relaxed))
+                    && !self.is_free.load(atomic::Ordering::Relaxed)
+                {
+                    thread::sleep(time::Duration::from_millis(100));
+                }
+                checkpoint = time::Instant::now();
                 if self.is_free.load(atomic::Ordering::Relaxed) {
                     break (1, total_count);
                 }
-                step_count = 0;
                 // info!("work id {} mining", self.worker_id);
             }
             if total_count > self.miner_hash_limit * 100000 {
@@ -132,13 +300,66 @@ impl Worker {
                 }
                 // break (2, total_count);
             }
+        };
+        self.report(step_count);
+        self.task_hashes.store(0, atomic::Ordering::Relaxed);
+        self.busy.store(false, atomic::Ordering::Relaxed);
+        ret
+    }
+
+    //限速休眠，新任务到达时提前结束
+    fn rest(&self, delay: time::Duration) {
+        let until = time::Instant::now() + delay;
+        loop {
+            let now = time::Instant::now();
+            if now >= until || self.is_free.load(atomic::Ordering::Relaxed) {
+                break;
+            }
+            thread::sleep((until - now).min(time::Duration::from_millis(100)));
         }
     }
 
+    //检查点上报计算次数，不加锁
+    fn report(&self, hashes: u64) {
+        self.hashes.fetch_add(hashes, atomic::Ordering::Relaxed);
+    }
+
+    //份额不中断当前任务，只上报当前 nonce
+    fn submit_share(&mut self, task: &Task) {
+        let share = task
+            .clone()
+            .with_worker_id(self.worker_id.clone())
+            .with_nonce(self.current_nonce)
+            .with_status(4);
+        self.found(task, true);
+        self.counter.add(share.clone());
+        self.sender.blocking_send(share).unwrap();
+    }
+
+    fn found(&self, task: &Task, share: bool) {
+        let job = task.job();
+        Event::SolutionFound {
+            worker: self.worker_id.clone(),
+            task_id: task.task_id(),
+            from: job.from,
+            to: job.to,
+            nonce: hex::encode(self.current_nonce),
+            hash: hex::encode(pow::hash(&self.current_nonce, &job.header)),
+            share,
+        }
+        .emit();
+    }
+
     pub fn notifier(&self) -> Notifier {
         Notifier {
             work_id: self.worker_id.clone(),
             is_free: self.is_free.clone(),
+            hashes: self.hashes.clone(),
+            task_hashes: self.task_hashes.clone(),
+            busy: self.busy.clone(),
+            paused: self.paused.clone(),
+            suspended: self.suspended.clone(),
+            stopped: self.stopped.clone(),
         }
     }
 
@@ -149,44 +370,20 @@ impl Worker {
         });
     }
 
-    fn double2(&self, job: &Job) -> Vec<u8> {
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(&self.current_nonce);
-        hasher.update(job.header.as_slice());
-        let hash1 = hasher.finalize();
-
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(hash1.as_bytes());
-        hasher.finalize().as_bytes().to_vec()
+    fn double2(&self, job: &Job) -> [u8; 32] {
+        pow::hash_with(self.hash_impl, &self.current_nonce, &job.header)
     }
 
-    fn check_target(hash: Vec<u8>, target: Vec<u8>) -> bool {
-        let zero_len = 32 - target.len();
-        let (zero_hash, non_zero_hash) = hash.split_at(zero_len);
-        for zero in zero_hash {
-            if *zero != 0 {
-                return false;
-            }
-        }
-        let mut i = 0;
-        for target_bytes in target {
-            if non_zero_hash[i] > target_bytes {
-                return false;
-            } else if non_zero_hash[i] < target_bytes {
-                return true;
-            }
-            i += 1;
-        }
-        true
+    fn check_target(hash: &[u8], target: &[u8]) -> bool {
+        pow::check_target(hash, target)
     }
 
-    fn check_hash(hash: Vec<u8>, target: Vec<u8>, from: u32, to: u32) -> bool {
-        Worker::check_target(hash.clone(), target) && Worker::check_index(hash, from, to)
+    fn check_hash(hash: &[u8], target: &[u8], from: u32, to: u32, groups: u32) -> bool {
+        Worker::check_target(hash, target) && Worker::check_index(hash, from, to, groups)
     }
 
-    fn check_index(hash: Vec<u8>, from: u32, to: u32) -> bool {
-        let big_index = (hash[31] % constant::CHAIN_NUMS) as u32;
-        (big_index / constant::GROUP_NUMS == from) && (big_index % constant::GROUP_NUMS == to)
+    fn check_index(hash: &[u8], from: u32, to: u32, groups: u32) -> bool {
+        pow::chain_index(hash, groups) == (from, to)
     }
 
     fn double(input: &[u8]) -> Hash {
@@ -196,15 +393,123 @@ impl Worker {
     fn hex_to_string(input: &[u8]) -> String {
         hex::encode(input)
     }
-
-    fn chain_index(from: u32, to: u32) -> u32 {
-        from * (constant::CHAIN_NUMS as u32) + to
-    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::ThrottleConfig;
+    use crate::model::{Job, WorkUnit};
+    use crate::task::Task;
+    use crate::throttle::Throttle;
     use crate::worker::Worker;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_sender, receiver) = crossbeam::channel::bounded(16);
+        let mut worker = Worker::new(tx, receiver);
+        let notifier = worker.notifier();
+        // unreachable target: mines until notified
+        let task = Task::new().with_job(Job {
+            target: vec![0; 32],
+            ..Default::default()
+        });
+        let handle = std::thread::spawn(move || worker.mining(&task));
+        std::thread::sleep(Duration::from_millis(500));
+        let reported = notifier.take_hashes();
+        assert!(reported > 0);
+        notifier.notify();
+        let (status, count) = handle.join().unwrap();
+        assert_eq!(status, 1);
+        assert_eq!(reported + notifier.take_hashes(), count);
+        assert!(!notifier.is_busy());
+    }
+
+    #[test]
+    fn test_pause() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_sender, receiver) = crossbeam::channel::bounded(16);
+        let mut worker = Worker::new(tx, receiver);
+        let notifier = worker.notifier();
+        notifier.pause();
+        let task = Task::new().with_job(Job {
+            target: vec![0; 32],
+            ..Default::default()
+        });
+        let handle = std::thread::spawn(move || worker.mining(&task));
+        // stuck at the first checkpoint
+        while notifier.task_hashes() == 0 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(notifier.is_busy());
+        let paused = notifier.task_hashes();
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(notifier.task_hashes(), paused);
+        notifier.resume();
+        while notifier.task_hashes() == paused {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // new jobs still interrupt a paused worker
+        notifier.pause();
+        notifier.notify();
+        assert_eq!(handle.join().unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_sender, receiver) = crossbeam::channel::bounded(16);
+        let conf = ThrottleConfig {
+            hash_rate: Some(200000f64),
+            ..Default::default()
+        };
+        let mut worker = Worker::new(tx, receiver)
+            .with_mining_steps(10000)
+            .with_throttle(Arc::new(RwLock::new(Throttle::new(&conf, 1))));
+        let notifier = worker.notifier();
+        let task = Task::new().with_job(Job {
+            target: vec![0; 32],
+            ..Default::default()
+        });
+        let handle = std::thread::spawn(move || worker.mining(&task));
+        std::thread::sleep(Duration::from_millis(1000));
+        let start = std::time::Instant::now();
+        notifier.notify();
+        let (status, count) = handle.join().unwrap();
+        assert_eq!(status, 1);
+        // capped at 200k hashes/s, one checkpoint of slack
+        assert!(count <= 200000 + 20002 * 2, "{}", count);
+        // the notification cuts the sleep short
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_stop() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (sender, receiver) = crossbeam::channel::bounded(16);
+        let mut worker = Worker::new(tx, receiver);
+        let notifier = worker.notifier();
+        let handle = std::thread::spawn(move || worker.work());
+        let task = Task::new().with_job(Job {
+            target: vec![0; 32],
+            ..Default::default()
+        });
+        sender.send(WorkUnit::TaskReq(task)).unwrap();
+        while !notifier.is_busy() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(notifier.state(), "mining");
+        // the interrupted task is handed back, then the thread ends with
+        // the queue still open
+        notifier.stop();
+        assert_eq!(rx.blocking_recv().unwrap().status(), 1);
+        handle.join().unwrap();
+        assert!(notifier.is_stopped());
+        drop(sender);
+    }
 
     #[test]
     fn test_double() {
@@ -223,8 +528,9 @@ mod tests {
     fn test_check_index() {
         let hex_str = "00000000aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaae";
         let hash = hex::decode(hex_str).unwrap();
-        assert!(Worker::check_index(hash.clone(), 3, 2));
-        assert!(!Worker::check_index(hash, 3, 3));
+        assert!(Worker::check_index(&hash, 3, 2, 4));
+        assert!(!Worker::check_index(&hash, 3, 3, 4));
+        assert!(Worker::check_index(&hash, 1, 0, 2));
     }
 
     #[test]
@@ -234,39 +540,42 @@ mod tests {
         let target =
             hex::decode("00000000aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")
                 .unwrap();
-        assert!(Worker::check_target(hash.clone(), target));
+        assert!(Worker::check_target(&hash, &target));
 
         // remove 4 leading zeros
         let target =
             hex::decode("0000aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
-        assert!(Worker::check_target(hash.clone(), target));
+        assert!(Worker::check_target(&hash, &target));
 
         // remove all leading zeros
         let target =
             hex::decode("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
-        assert!(Worker::check_target(hash.clone(), target));
+        assert!(Worker::check_target(&hash, &target));
 
         // remove all leading zeros + "aa"
         let target = hex::decode("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
-        assert!(!Worker::check_target(hash.clone(), target));
+        assert!(!Worker::check_target(&hash, &target));
 
         // replace leading "aa" with "bb"
         let target =
             hex::decode("bbaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
-        assert!(Worker::check_target(hash.clone(), target));
+        assert!(Worker::check_target(&hash, &target));
 
         // replace the last "a" with "b"
         let target =
             hex::decode("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaab").unwrap();
-        assert!(Worker::check_target(hash.clone(), target));
+        assert!(Worker::check_target(&hash, &target));
 
         // replace the last "a" with "9"
         let target =
             hex::decode("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa9").unwrap();
-        assert!(!Worker::check_target(hash.clone(), target));
+        assert!(!Worker::check_target(&hash, &target));
 
         let target =
             hex::decode("a9aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
-        assert!(!Worker::check_target(hash.clone(), target));
+        assert!(!Worker::check_target(&hash, &target));
+
+        // a target longer than the hash is never met
+        assert!(!Worker::check_target(&hash, &[0xff; 33]));
     }
 }
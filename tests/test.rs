@@ -1 +1,68 @@
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
 
+const BIN: &str = env!("CARGO_BIN_EXE_alephium-miner");
+
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn wait_listening(port: u16) {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "port {} not open",
+            port
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn test_mine_against_mock_node() {
+    let port = free_port();
+    let mut node = Process(
+        Command::new(BIN)
+            .args(["mock-node", "--listen"])
+            .arg(format!("127.0.0.1:{}", port))
+            .args(["--blocks", "3"])
+            .spawn()
+            .unwrap(),
+    );
+    wait_listening(port);
+    let _miner = Process(
+        Command::new(BIN)
+            .args(["--ip", "127.0.0.1", "--port"])
+            .arg(port.to_string())
+            .args(["--worker", "2"])
+            .spawn()
+            .unwrap(),
+    );
+
+    // the mock node exits successfully once it accepted 3 blocks
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = node.0.try_wait().unwrap() {
+            break status;
+        }
+        assert!(start.elapsed() < Duration::from_secs(60), "no blocks mined");
+        thread::sleep(Duration::from_millis(100));
+    };
+    assert!(status.success());
+}